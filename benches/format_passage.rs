@@ -0,0 +1,87 @@
+//! Times formatting a 10,000-note passage against boxed `Grouping`s and against
+//! `StaticGrouping`s, and joining a `String` per note against writing into one buffer. Run with
+//! `cargo bench`.
+
+extern crate scritto;
+
+use std::time::{Duration, Instant};
+
+use scritto::RatioDuration;
+use scritto::notes::{ETPitch, SingleNote};
+use scritto::scrittore::{Notes, format_note, format_notes, format_notes_into};
+use scritto::sequenza::{Beat, Measure, Grouping, GroupingController, StaticGrouping};
+
+const NOTES: usize = 10_000;
+const MEASURES: usize = NOTES / 4;
+const RUNS: u32 = 20;
+
+fn passage() -> Vec<SingleNote<ETPitch, RatioDuration>> {
+    (0..NOTES)
+        .map(|i| SingleNote::new(60 + (i % 12) as u32, RatioDuration(1, 4)))
+        .collect()
+}
+
+fn boxed_groupings() -> Vec<Box<dyn Grouping<RatioDuration>>> {
+    (0..MEASURES).map(|_| {
+        let beats = (0..4)
+            .map(|_| Box::new(Beat::new_ratio(1, 4)) as Box<dyn Grouping<RatioDuration>>)
+            .collect();
+        Box::new(Measure::from_contents(beats)) as Box<dyn Grouping<RatioDuration>>
+    }).collect()
+}
+
+fn static_groupings() -> Vec<StaticGrouping<RatioDuration>> {
+    (0..MEASURES).map(|_| {
+        StaticGrouping::measure((0..4).map(|_| StaticGrouping::beat(1, 4)).collect())
+    }).collect()
+}
+
+fn time<F>(name: &str, mut run: F) 
+    where F: FnMut() -> usize
+{
+    let mut total = Duration::new(0, 0);
+    let mut length = 0;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        length = run();
+        total += start.elapsed();
+    }
+    println!("{:>8}: {:>8.3} ms per run ({} bytes)", name, 
+             total.as_secs_f64() * 1000.0 / RUNS as f64, length);
+}
+
+fn main() {
+    let data = passage();
+    let notes = Notes::new(data.clone());
+
+    time("boxed", || {
+        let groupings = boxed_groupings();
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        format_notes(&notes, &mut controller).unwrap().len()
+    });
+
+    time("static", || {
+        let groupings = static_groupings();
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        format_notes(&notes, &mut controller).unwrap().len()
+    });
+
+    time("joined", || {
+        let groupings = static_groupings();
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        data.iter()
+            .map(|note| format_note(note, &mut controller).unwrap())
+            .collect::<Vec<String>>()
+            .join(" ")
+            .len()
+    });
+
+    let mut buffer = String::new();
+    time("buffered", || {
+        buffer.clear();
+        let groupings = static_groupings();
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        format_notes_into(&notes, &mut controller, &mut buffer).unwrap();
+        buffer.len()
+    });
+}
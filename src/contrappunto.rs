@@ -0,0 +1,82 @@
+//! The `contrappunto` module checks pairs of voices against the rules of voice leading. Voices
+//! are compared note by note, so the nth note of one voice is taken to sound against the nth
+//! note of the other, regardless of their durations.
+
+use super::Durational;
+use super::notes::{ETPitch, SingleNote};
+use super::scrittore::Notes;
+
+/// The kinds of voice leading that the checker reports.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ViolationKind {
+    ParallelFifths,
+    ParallelOctaves
+}
+
+/// A voice-leading error between two voices. The `index` is the position of the second of the
+/// two notes that form the forbidden motion.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    pub index: usize,
+    pub kind: ViolationKind
+}
+
+/// Finds every place where `upper` and `lower` move in the same direction from one perfect fifth
+/// to another, or from one octave (or unison) to another. Compound intervals count as their
+/// simple forms. Voices of different lengths cannot be aligned, and are an error.
+pub fn parallels<D>(upper: &Notes<SingleNote<ETPitch, D>>, lower: &Notes<SingleNote<ETPitch, D>>) -> Result<Vec<Violation>, &'static str> 
+where D: Durational
+{
+    let (upper, lower) = (upper.as_slice(), lower.as_slice());
+    if upper.len() != lower.len() {
+        return Err("Voices must have the same number of notes");
+    }
+    let mut violations = Vec::new();
+    for index in 1..upper.len() {
+        let (upper_from, upper_to) = (upper[index - 1].pitch(), upper[index].pitch());
+        let (lower_from, lower_to) = (lower[index - 1].pitch(), lower[index].pitch());
+        let upper_motion = upper_from.interval_to(upper_to).signum();
+        let lower_motion = lower_from.interval_to(lower_to).signum();
+        // Both voices must actually move, and in the same direction
+        if upper_motion == 0 || upper_motion != lower_motion {
+            continue;
+        }
+        let from = lower_from.interval_to(upper_from).rem_euclid(12);
+        let to = lower_to.interval_to(upper_to).rem_euclid(12);
+        let kind = match (from, to) {
+            (7, 7) => ViolationKind::ParallelFifths,
+            (0, 0) => ViolationKind::ParallelOctaves,
+            _ => continue
+        };
+        violations.push(Violation { index, kind });
+    }
+    Ok(violations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parallel_fifths() {
+        let upper = Notes::from_lilypond("g'4 a' b' c''").unwrap();
+        let lower = Notes::from_lilypond("c'4 d' g c'").unwrap();
+        let violations = parallels(&upper, &lower).unwrap();
+        assert_eq!(violations, vec![Violation { index: 1, kind: ViolationKind::ParallelFifths }]);
+    }
+
+    #[test]
+    fn test_parallel_octaves() {
+        // Oblique motion into an octave, then similar motion between octaves two apart
+        let upper = Notes::from_lilypond("c''4 c'' d''").unwrap();
+        let lower = Notes::from_lilypond("e4 c d").unwrap();
+        let violations = parallels(&upper, &lower).unwrap();
+        assert_eq!(violations, vec![Violation { index: 2, kind: ViolationKind::ParallelOctaves }]);
+
+        // Contrary motion between octaves is allowed
+        let upper = Notes::from_lilypond("c''4 d'").unwrap();
+        let lower = Notes::from_lilypond("c4 d").unwrap();
+        assert!(parallels(&upper, &lower).unwrap().is_empty());
+        assert!(parallels(&upper, &Notes::from_lilypond("c4").unwrap()).is_err());
+    }
+}
@@ -0,0 +1,111 @@
+//! The `insieme` module provides pitch-class set analysis over `ETPitch` values: normal form,
+//! prime form and the interval-class vector, as used in post-tonal theory. Octaves and spelling
+//! are discarded, and every pitch is reduced to `midi % 12`.
+
+use super::notes::ETPitch;
+
+/// An unordered collection of distinct pitch classes, from 0 (C) to 11 (B).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PitchClassSet {
+    classes: Vec<u32>
+}
+
+impl PitchClassSet {
+    /// Creates a set from any pitch classes, reducing each one modulo 12.
+    pub fn new(classes: &[u32]) -> Self {
+        let mut classes: Vec<u32> = classes.iter().map(|pc| pc % 12).collect();
+        classes.sort();
+        classes.dedup();
+        PitchClassSet { classes }
+    }
+
+    pub fn from_pitches(pitches: &[ETPitch]) -> Self {
+        let classes: Vec<u32> = pitches.iter().map(|pitch| pitch.midi).collect();
+        PitchClassSet::new(&classes)
+    }
+
+    /// The pitch classes in ascending order.
+    pub fn classes(&self) -> &[u32] {
+        &self.classes
+    }
+
+    /// The rotation of the set spanning the smallest interval. Ties are broken (following Rahn)
+    /// by the smallest interval from the first to the second-to-last pitch class, and so on
+    /// inward, and then by the lowest first pitch class.
+    pub fn normal_form(&self) -> Vec<u32> {
+        let len = self.classes.len();
+        (0..len)
+            .map(|start| {
+                (0..len).map(|i| self.classes[(start + i) % len]).collect::<Vec<u32>>()
+            })
+            .min_by_key(|rotation| (packing(rotation), rotation[0]))
+            .unwrap_or_default()
+    }
+
+    /// The most compact form of the set or its inversion, transposed to begin on 0.
+    pub fn prime_form(&self) -> Vec<u32> {
+        let inversion = PitchClassSet::new(&self.classes.iter().map(|pc| 12 - pc).collect::<Vec<u32>>());
+        let original = transpose_to_zero(&self.normal_form());
+        let inverted = transpose_to_zero(&inversion.normal_form());
+        if packing(&inverted) < packing(&original) {
+            inverted
+        } else {
+            original
+        }
+    }
+
+    /// The number of times each interval class, from 1 (semitone) to 6 (tritone), appears
+    /// between pairs of pitch classes in the set.
+    pub fn interval_vector(&self) -> [u32; 6] {
+        let mut vector = [0; 6];
+        for (i, a) in self.classes.iter().enumerate() {
+            for b in self.classes[i + 1..].iter() {
+                let interval = (b + 12 - a) % 12;
+                let class = if interval > 6 { 12 - interval } else { interval };
+                vector[class as usize - 1] += 1;
+            }
+        }
+        vector
+    }
+}
+
+/// Intervals from the first pitch class to each of the others, from the last inward. Lower values
+/// are more tightly packed.
+fn packing(rotation: &[u32]) -> Vec<u32> {
+    rotation.iter().skip(1).rev()
+        .map(|pc| (pc + 12 - rotation[0]) % 12)
+        .collect()
+}
+
+fn transpose_to_zero(classes: &[u32]) -> Vec<u32> {
+    classes.iter().map(|pc| (pc + 12 - classes[0]) % 12).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prime_form_augmented_triad() {
+        let set = PitchClassSet::from_pitches(&[ETPitch::new(68), ETPitch::new(60), ETPitch::new(76)]);
+        assert_eq!(set.normal_form(), vec![0, 4, 8]);
+        assert_eq!(set.prime_form(), vec![0, 4, 8]);
+        assert_eq!(set.interval_vector(), [0, 0, 0, 3, 0, 0]);
+    }
+
+    #[test]
+    fn test_prime_form_major_triad() {
+        assert_eq!(PitchClassSet::new(&[0, 4, 7]).prime_form(), vec![0, 3, 7]);
+        let set = PitchClassSet::new(&[2, 6, 9]);
+        assert_eq!(set.normal_form(), vec![2, 6, 9]);
+        assert_eq!(set.prime_form(), vec![0, 3, 7]);
+        assert_eq!(set.interval_vector(), [0, 0, 1, 1, 1, 0]);
+    }
+
+    #[test]
+    fn test_normal_form_wraps_around() {
+        let set = PitchClassSet::new(&[0, 10, 11, 1]);
+        assert_eq!(set.normal_form(), vec![10, 11, 0, 1]);
+        assert_eq!(set.prime_form(), vec![0, 1, 2, 3]);
+    }
+}
@@ -1,20 +1,28 @@
 extern crate handlebars;
 
-#[allow(unused)]
 #[macro_use] extern crate serde_derive;
 
 extern crate serde;
 
-#[allow(unused)]
 #[macro_use] extern crate serde_json;
 extern crate serde_test;
+#[cfg(feature = "rayon")] extern crate rayon;
 
 pub mod notes;
 pub mod sequenza;
 pub mod scrittore;
+pub mod partitura;
+pub mod insieme;
+pub mod contrappunto;
 
 use std::ops::{Add, Sub};
-use std::cmp::{PartialOrd, PartialEq, Ordering};
+use std::cmp::{self, PartialOrd, PartialEq, Ordering};
+use std::error::Error;
+use std::fmt;
+
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::ser::{SerializeStruct, SerializeTupleStruct};
+use serde::de::{self, Visitor, SeqAccess, Unexpected};
 
 pub use notes::Note;
 pub use sequenza::Grouping;
@@ -23,7 +31,7 @@ pub use sequenza::Grouping;
 /// the `new` function necessary to allow other potentials for duration.
 pub trait Durational: Copy + PartialEq {
     /// Returns a new Durational object. Probably should be axed.
-    fn new(u32, u32) -> Self;
+    fn new(numerator: u32, denominator: u32) -> Self;
     fn as_ratio(&self) -> (u32, u32);
     fn as_float(&self) -> f64 {
         let ratio = self.as_ratio();
@@ -33,43 +41,95 @@ pub trait Durational: Copy + PartialEq {
     fn as_lilypond(&self) -> String {
         String::new()
     }
+
+    /// The longest value, no longer than this one, that `as_lilypond` can write as a single
+    /// note. A duration that can be written as it is returns itself, which is the default.
+    fn longest_printable(&self) -> Self {
+        *self
+    }
 }
 
 /// Wrapper for any struct implementing `Durational`, which is necessary in order to avoid the
 /// [orphan trait constraint](https://doc.rust-lang.org/error-index.html#E0210). This allows
 /// implementation of `std::ops` traits to make it easier to write generic code over various
 /// `Durational` types.
-#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+///
+/// Durations are compared by the value of their ratios rather than their terms, so that 2/6 is
+/// equal to 1/3.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct Duration<D: Durational>(pub D);
 
+impl<D> Duration<D> 
+where D: Durational
+{
+    /// Renders the duration as it is written inside of a tuplet. The `ratio` is the number of
+    /// written notes to the number of notes whose time they take (3/2 for a triplet), and nested
+    /// tuplets are rendered by passing the product of all of their ratios.
+    pub fn as_lilypond_in_tuplet(&self, ratio: (u32, u32)) -> String {
+        let (num, den) = self.as_ratio();
+        let (num, den) = (num * ratio.0, den * ratio.1);
+        let least = gcd(num, den);
+        Duration::<D>::new(num / least, den / least).as_lilypond()
+    }
+
+    /// Adds two durations like `+`, except that a sum whose denominator would be larger than
+    /// `max_denominator` is rounded to the nearest multiple of `1 / max_denominator` instead. The
+    /// flag is `true` when the sum had to be approximated. This keeps long runs of mixed tuplets,
+    /// whose denominators multiply together, from overflowing.
+    pub fn add_capped(self, other: Self, max_denominator: u32) -> (Self, bool) {
+        let ratio = combine(self.as_ratio(), other.as_ratio(), false);
+        let ((num, den), approximated) = cap(ratio, max_denominator);
+        (Duration::new(num, den), approximated)
+    }
+
+    /// Subtracts `other` like `-` (stopping at zero), approximating the difference as in
+    /// `add_capped`.
+    pub fn sub_capped(self, other: Self, max_denominator: u32) -> (Self, bool) {
+        let ratio = combine(self.as_ratio(), other.as_ratio(), true);
+        let ((num, den), approximated) = cap(ratio, max_denominator);
+        (Duration::new(num, den), approximated)
+    }
+
+    /// The same duration with its ratio in lowest terms, so that 4/8 becomes 1/2.
+    pub fn reduced(&self) -> Duration<D> {
+        let (num, den) = self.as_ratio();
+        match gcd(num, den) {
+            0 => *self,
+            least => Duration::new(num / least, den / least)
+        }
+    }
+}
+
+impl<D> PartialEq for Duration<D> 
+where D: Durational
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+/// Ratios are cross-multiplied rather than divided out, so that no precision is lost comparing
+/// durations such as thirds, which can't be written exactly as floats.
 impl<D> PartialOrd for Duration<D> 
-where D: Durational + PartialEq
+where D: Durational
 {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.as_float().partial_cmp(&other.as_float())
+        let (a, b) = self.as_ratio();
+        let (c, d) = other.as_ratio();
+        Some((a as u64 * d as u64).cmp(&(c as u64 * b as u64)))
     }
 }
 
+/// A duration can't be negative, so taking a longer duration from a shorter one leaves nothing
+/// rather than underflowing: 1/8 - 1/4 is 0.
 impl<D> Sub for Duration<D> 
 where D: Durational
 {
     type Output = Self;
 
     fn sub(self, other: Self) -> Self {
-        let mut ratio = self.as_ratio();
-        let mut other = other.as_ratio();
-        let mult = lcm(ratio.1, other.1);
-        let r1_scale = mult / ratio.1;
-        let r2_scale = mult / other.1;
-        ratio.0 *= r1_scale;
-        ratio.1 *= r1_scale;
-        other.0 *= r2_scale;
-        other.1 *= r2_scale;
-        ratio.0 -= other.0;
-        let least = gcd(ratio.0, ratio.1);
-        ratio.0 /= least;
-        ratio.1 /= least;
-        Duration(D::new(ratio.0, ratio.1))
+        let (num, den) = combine(self.as_ratio(), other.as_ratio(), true);
+        Duration(D::new(narrow(num), narrow(den)))
     }
 }
 
@@ -79,20 +139,8 @@ where D: Durational
     type Output = Self;
 
     fn add(self, other: Self) -> Self {
-        let mut ratio = self.as_ratio();
-        let mut other = other.as_ratio();
-        let mult = lcm(ratio.1, other.1);
-        let r1_scale = mult / ratio.1;
-        let r2_scale = mult / other.1;
-        ratio.0 *= r1_scale;
-        ratio.1 *= r1_scale;
-        other.0 *= r2_scale;
-        other.1 *= r2_scale;
-        ratio.0 += other.0;
-        let least = gcd(ratio.0, ratio.1);
-        ratio.0 /= least;
-        ratio.1 /= least;
-        Duration(D::new(ratio.0, ratio.1))
+        let (num, den) = combine(self.as_ratio(), other.as_ratio(), false);
+        Duration(D::new(narrow(num), narrow(den)))
     }
 }
 
@@ -124,36 +172,154 @@ where D: Durational
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
-struct IntegerDuration(u32);
+/// Serializes a `Duration` as an object such as `{"num":3,"den":4,"as_lilypond":"2."}` rather
+/// than the bare `[3,4]` of its own `Serialize`, so that JSON scores read on their own. Durations
+/// with no single written value, such as 5/8, a tuplet value such as 1/3, or nothing at all, have
+/// a null `as_lilypond`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DurationObject<D: Durational>(pub Duration<D>);
+
+impl<D> Serialize for DurationObject<D> 
+where D: Durational
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> 
+        where S: Serializer
+    {
+        let (num, den) = self.0.as_ratio();
+        // `longest_printable` hands back tuplet values unchanged, so they are ruled out first
+        let (least_num, least_den) = self.0.reduced().as_ratio();
+        let printable = least_num > 0 && least_den.is_power_of_two()
+            && Duration((self.0).0.longest_printable()) == self.0;
+        let mut s = serializer.serialize_struct("Duration", 3)?;
+        s.serialize_field("num", &num)?;
+        s.serialize_field("den", &den)?;
+        s.serialize_field("as_lilypond", &if printable { Some(self.0.as_lilypond()) } else { None })?;
+        s.end()
+    }
+}
+
+impl<D> From<Duration<D>> for DurationObject<D> 
+where D: Durational
+{
+    fn from(duration: Duration<D>) -> Self {
+        DurationObject(duration)
+    }
+}
+
+/// A whole number of some note value, which is a whole note unless another one is given. Counting
+/// in quarters, as many sequencers do, `IntegerDuration::counting(3, 4)` is three quarter notes.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct IntegerDuration {
+    count: u32,
+    unit: u32
+}
+
+impl IntegerDuration {
+    /// `count` notes of the value `1 / unit`, so that a `unit` of 4 counts in quarter notes.
+    ///
+    /// Panics if `unit` is not a power of two, since only those name a note value.
+    pub fn counting(count: u32, unit: u32) -> Self {
+        if !unit.is_power_of_two() {
+            panic!("Can't count in notes of 1/{}", unit);
+        }
+        IntegerDuration { count, unit }
+    }
+}
 
 impl Durational for IntegerDuration {
-    fn new(n: u32, _: u32) -> IntegerDuration {
-        IntegerDuration(n)
+    /// `n` whole notes when `d` is 1, as before units were added. Any other `d` gives the ratio
+    /// `n / d`, counted in notes of `1 / d` once it is reduced, so that arithmetic on durations
+    /// counted in quarters stays exact. Panics if the reduced `d` is not a power of two.
+    fn new(n: u32, d: u32) -> IntegerDuration {
+        if d.is_power_of_two() {
+            return IntegerDuration::counting(n, d);
+        }
+        let least = cmp::max(gcd(n, d), 1);
+        IntegerDuration::counting(n / least, d / least)
     }
 
     fn as_ratio(&self) -> (u32, u32) {
-        (self.0, 1)
+        (self.count, self.unit)
     }
 
+    /// Writes the count as a multiple of the unit, such as `4*3` for three quarter notes.
     fn as_lilypond(&self) -> String {
-        match self.as_ratio() {
-            (x, 1) => {
-                format!("1*{}", x.to_string())
-            },
-            _ => { String::new() }
-        }
+        format!("{}*{}", self.unit, self.count)
     }
 }
 
 impl From<u32> for Duration<IntegerDuration> {
     fn from(x: u32) -> Duration<IntegerDuration> {
-        Duration(IntegerDuration(x))
+        Duration(IntegerDuration::counting(x, 1))
+    }
+}
+
+/// Counting in whole notes, an `IntegerDuration` is written as the bare count. Any other unit is
+/// written as a pair of the count and the unit.
+impl Serialize for IntegerDuration
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> 
+        where S: Serializer
+    {
+        if self.unit == 1 {
+            serializer.serialize_newtype_struct("IntegerDuration", &self.count)
+        } else {
+            let mut s = serializer.serialize_tuple_struct("IntegerDuration", 2)?;
+            s.serialize_field(&self.count)?;
+            s.serialize_field(&self.unit)?;
+            s.end()
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for IntegerDuration
+{
+    fn deserialize<De>(deserializer: De) -> Result<Self, De::Error> 
+        where De: Deserializer<'de>
+    {
+        deserializer.deserialize_any(IntegerDurationVisitor)
+    }
+}
+
+struct IntegerDurationVisitor;
+
+impl<'de> Visitor<'de> for IntegerDurationVisitor {
+    type Value = IntegerDuration;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a count of whole notes, or a count and a unit")
+    }
+
+    fn visit_u64<E>(self, count: u64) -> Result<IntegerDuration, E> 
+        where E: de::Error
+    {
+        if count > u32::MAX as u64 {
+            return Err(E::custom("count is too large"));
+        }
+        Ok(IntegerDuration::counting(count as u32, 1))
+    }
+
+    fn visit_newtype_struct<De>(self, deserializer: De) -> Result<IntegerDuration, De::Error> 
+        where De: Deserializer<'de>
+    {
+        let count = u32::deserialize(deserializer)?;
+        Ok(IntegerDuration::counting(count, 1))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<IntegerDuration, A::Error> 
+        where A: SeqAccess<'de>
+    {
+        let count = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let unit: u32 = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        if !unit.is_power_of_two() {
+            return Err(de::Error::invalid_value(Unexpected::Unsigned(unit as u64), &"a power of two"));
+        }
+        Ok(IntegerDuration::counting(count, unit))
     }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
-struct RatioDuration(pub u32, pub u32);
+pub struct RatioDuration(pub u32, pub u32);
 
 impl Durational for RatioDuration {
     fn new(n: u32, d: u32) -> RatioDuration {
@@ -169,12 +335,70 @@ impl Durational for RatioDuration {
             (1, x) if x.is_power_of_two() => { 
                 x.to_string() 
             }
-            (3, x) if x.is_power_of_two() => { 
-                format!("{}.", x.to_string())
+            (3, x) if x.is_power_of_two() && x > 1 => { 
+                format!("{}.", x / 2)
             }
+            // Values longer than a whole note have names of their own
+            (2, 1) => "\\breve".to_string(),
+            (3, 1) => "\\breve.".to_string(),
+            (4, 1) => "\\longa".to_string(),
+            (6, 1) => "\\longa.".to_string(),
             (x, y) => { panic!("Could not print {}/{}", x, y) }
         }
     }
+
+    /// Picks the longest plain or dotted value that fits, up to a dotted longa, so that 5/8 gives
+    /// a half note and 7/8 a dotted half. Ratios whose denominator isn't a power of two can't be
+    /// broken down this way, and are returned as they are.
+    fn longest_printable(&self) -> Self {
+        let (num, den) = (self.0 as u64, self.1 as u64);
+        if num == 0 || !den.is_power_of_two() {
+            return *self;
+        }
+        let plain = cmp::min(1 << (63 - num.leading_zeros()), 4 * den);
+        let dotted = plain + plain / 2;
+        let value = if plain > 1 && dotted <= num && dotted <= 6 * den { dotted } else { plain };
+        let least = gcd_u128(value as u128, den as u128) as u64;
+        RatioDuration((value / least) as u32, (den / least) as u32)
+    }
+}
+
+impl RatioDuration {
+    /// Reads a Lilypond duration, such as `4` or `8..`.
+    pub fn from_lilypond(text: &str) -> Result<RatioDuration, ParseError> {
+        let digits = text.trim_end_matches('.');
+        let dots = (text.len() - digits.len()) as u32;
+        let value: u32 = digits.parse()
+            .map_err(|_| ParseError::new(text, "Could not read duration"))?;
+        if !value.is_power_of_two() {
+            return Err(ParseError::new(text, "Duration is not a power of two"));
+        }
+        // Each dot adds half of the previous value: n dots make (2^(n+1) - 1) / (value * 2^n)
+        let too_short = || ParseError::new(text, "Duration is too short to hold");
+        let scale = 1u32.checked_shl(dots).filter(|scale| *scale <= u32::MAX / 2).ok_or_else(too_short)?;
+        let den = value.checked_mul(scale).ok_or_else(too_short)?;
+        Ok(RatioDuration(2 * scale - 1, den))
+    }
+}
+
+impl Duration<RatioDuration> {
+    /// Rounds a length in whole notes to the nearest multiple of `1 / denominator`, so that
+    /// `from_float(0.26, 16)` is a quarter note. The result is reduced to lowest terms. A
+    /// `denominator` of zero leaves nothing to round to, and is an error.
+    pub fn from_float(whole_notes: f64, denominator: u32) -> Result<Self, &'static str> {
+        if denominator == 0 {
+            return Err("Denominator must be at least one");
+        }
+        let units = (whole_notes * denominator as f64).round().max(0.) as u32;
+        let least = gcd(units, denominator);
+        Ok(Duration(RatioDuration(units / least, denominator / least)))
+    }
+}
+
+impl From<(u32, u32)> for Duration<RatioDuration> {
+    fn from(ratio: (u32, u32)) -> Duration<RatioDuration> {
+        Duration(RatioDuration(ratio.0, ratio.1))
+    }
 }
 
 fn gcd(a: u32, b: u32) -> u32 {
@@ -188,10 +412,78 @@ fn gcd(a: u32, b: u32) -> u32 {
     (n as f64).abs() as u32
 }
 
-fn lcm(a: u32, b: u32) -> u32 {
-    (a * b) / gcd(a, b)
+fn lcm(a: u128, b: u128) -> u128 {
+    a / gcd_u128(a, b) * b
+}
+
+/// Adds or subtracts two ratios in lowest terms, widening to `u128` so that neither the common
+/// denominator nor the numerators scaled up to it can overflow.
+fn combine(a: (u32, u32), b: (u32, u32), subtract: bool) -> (u128, u128) {
+    let (a_num, a_den) = (a.0 as u128, a.1 as u128);
+    let (b_num, b_den) = (b.0 as u128, b.1 as u128);
+    let den = lcm(a_den, b_den);
+    let (a_num, b_num) = (a_num * (den / a_den), b_num * (den / b_den));
+    let num = if subtract { a_num.saturating_sub(b_num) } else { a_num + b_num };
+    match gcd_u128(num, den) {
+        0 => (num, den),
+        least => (num / least, den / least)
+    }
+}
+
+/// Rounds a ratio to the nearest multiple of `1 / max_denominator` if its denominator is too
+/// large, returning the (reduced) ratio and whether it was rounded.
+fn cap(ratio: (u128, u128), max_denominator: u32) -> ((u32, u32), bool) {
+    let (num, den) = ratio;
+    if den <= max_denominator as u128 {
+        return ((narrow(num), narrow(den)), false);
+    }
+    let max = max_denominator as u128;
+    let units = (2 * num * max + den) / (2 * den);
+    let least = gcd_u128(units, max);
+    ((narrow(units / least), narrow(max / least)), true)
+}
+
+fn gcd_u128(a: u128, b: u128) -> u128 {
+    let (mut m, mut n) = (a, b);
+    while m != 0 {
+        let temp = m;
+        m = n % temp;
+        n = temp;
+    }
+    n
+}
+
+fn narrow(value: u128) -> u32 {
+    if value > u32::MAX as u128 {
+        panic!("Duration of {} is too large to hold; try add_capped", value);
+    }
+    value as u32
+}
+
+/// Error produced when reading Lilypond input, carrying the text that could not be read.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub token: String,
+    pub reason: &'static str
+}
+
+impl ParseError {
+    pub fn new(token: &str, reason: &'static str) -> Self {
+        ParseError {
+            token: token.to_string(),
+            reason
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: \"{}\"", self.reason, self.token)
+    }
 }
 
+impl Error for ParseError {}
+
 /// Responsible in many of the in-house stock cases for translating the onset of the `Note` into
 /// text. This includes 12-tone equal tempered pitches (which are provided) as well as rational
 /// pitches that take the form of the Helmholtz-Ellis accidentals as written in the Lilypond HE
@@ -212,11 +504,47 @@ mod tests {
 
     #[test]
     fn subtract_duration() {
-        let dur1 = Duration(IntegerDuration(6));
-        let dur2 = Duration(IntegerDuration(4));
-        assert_eq!(dur1 - dur2, Duration(IntegerDuration(2)));
+        let dur1 = Duration(IntegerDuration::counting(6, 1));
+        let dur2 = Duration(IntegerDuration::counting(4, 1));
+        assert_eq!(dur1 - dur2, Duration(IntegerDuration::counting(2, 1)));
     }
     
+    #[test]
+    fn serialize_duration_object() {
+        let object = DurationObject(Duration(RatioDuration(3, 4)));
+        assert_eq!(serde_json::to_value(object).unwrap(), 
+                   json!({ "num": 3, "den": 4, "as_lilypond": "2." }));
+        assert_eq!(serde_json::to_string(&DurationObject(Duration(RatioDuration(5, 8)))).unwrap(), 
+                   r#"{"num":5,"den":8,"as_lilypond":null}"#);
+        assert_eq!(serde_json::to_string(&DurationObject(Duration(RatioDuration(1, 3)))).unwrap(), 
+                   r#"{"num":1,"den":3,"as_lilypond":null}"#);
+        assert_eq!(serde_json::to_string(&DurationObject(Duration(RatioDuration(0, 1)))).unwrap(), 
+                   r#"{"num":0,"den":1,"as_lilypond":null}"#);
+        assert_eq!(serde_json::to_string(&Duration(RatioDuration(3, 4))).unwrap(), "[3,4]");
+    }
+
+    #[test]
+    fn subtract_longer_duration() {
+        let eighth = Duration(RatioDuration(1, 8));
+        let quarter = Duration(RatioDuration(1, 4));
+        assert_eq!((eighth - quarter).as_ratio(), (0, 1));
+        assert_eq!(eighth.sub_capped(quarter, 16), (Duration(RatioDuration(0, 1)), false));
+        assert_eq!(quarter - eighth, eighth);
+    }
+
+    #[test]
+    fn compare_thirds() {
+        let third = Duration(RatioDuration(1, 3));
+        let sum = Duration(RatioDuration(1, 6)) + Duration(RatioDuration(1, 6));
+        let difference = Duration(RatioDuration(1, 2)) - Duration(RatioDuration(1, 6));
+        assert_eq!(sum, third);
+        assert_eq!(difference, third);
+        assert_eq!(Duration(RatioDuration(2, 6)), third);
+        assert_eq!(Duration(RatioDuration(2, 6)).partial_cmp(&third), Some(Ordering::Equal));
+        assert!(Duration(RatioDuration(1, 3)) < Duration(RatioDuration(1, 2)));
+        assert!(Duration(RatioDuration(1, 3)) > Duration(RatioDuration(33, 100)));
+    }
+
     #[test]
     fn test_lcm() {
         assert_eq!(lcm(6, 8), 24);
@@ -240,6 +568,51 @@ mod tests {
         assert_eq!(dur.as_float(), 0.25);
     }
 
+    #[test]
+    fn add_prime_tuplets() {
+        let primes = [19, 23, 29, 31, 37, 41, 43];
+        let mut sum = Duration(RatioDuration(0, 1));
+        let mut approximated = false;
+        for prime in primes.iter() {
+            let (next, rounded) = sum.add_capped(Duration(RatioDuration(1, *prime)), 1 << 16);
+            sum = next;
+            approximated |= rounded;
+        }
+        assert!(approximated);
+        let exact: f64 = primes.iter().map(|prime| 1. / *prime as f64).sum();
+        assert!((sum.as_float() - exact).abs() < 7. / (1 << 16) as f64);
+
+        // Small sums are exact, and reduced
+        let (sum, rounded) = Duration(RatioDuration(1, 3)).add_capped(Duration(RatioDuration(1, 6)), 1 << 16);
+        assert_eq!((sum, rounded), (Duration(RatioDuration(1, 2)), false));
+        assert_eq!(lcm(1 << 20, 3 << 20), 3 << 20);
+    }
+
+    #[test]
+    fn capped_near_u32_max() {
+        // The common denominator of these is nearly 2^64, and so are both scaled numerators
+        let a = Duration(RatioDuration(u32::MAX - 1, u32::MAX));
+        let b = Duration(RatioDuration(u32::MAX - 2, u32::MAX - 1));
+        assert_eq!(a.add_capped(b, 1 << 16), (Duration(RatioDuration(2, 1)), true));
+        assert_eq!(a.sub_capped(b, 1 << 16), (Duration(RatioDuration(0, 1)), true));
+        assert_eq!(a.add_capped(Duration(RatioDuration(1, u32::MAX)), u32::MAX), (Duration(RatioDuration(1, 1)), false));
+    }
+
+    #[test]
+    fn reduced() {
+        assert_eq!(Duration(RatioDuration(4, 8)).reduced(), Duration(RatioDuration(1, 2)));
+        assert_eq!(Duration(RatioDuration(3, 8)).reduced(), Duration(RatioDuration(3, 8)));
+        assert_eq!(Duration(RatioDuration(0, 4)).reduced(), Duration(RatioDuration(0, 1)));
+    }
+
+    #[test]
+    fn from_float() {
+        assert_eq!(Duration::from_float(0.26, 16), Ok(Duration(RatioDuration(1, 4))));
+        assert_eq!(Duration::from_float(0.375, 16), Ok(Duration(RatioDuration(3, 8))));
+        assert_eq!(Duration::from_float(0., 16), Ok(Duration(RatioDuration(0, 1))));
+        assert!(Duration::from_float(0.25, 0).is_err());
+    }
+
     #[test]
     fn as_lilypond() {
         let dur = Duration(RatioDuration(1, 1));
@@ -248,10 +621,49 @@ mod tests {
 
     #[test]
     fn as_lilypond_dotted() {
-        let dur = Duration(RatioDuration(3, 4));
+        let dur = Duration(RatioDuration(3, 8));
         assert_eq!(dur.as_lilypond(), "4.");
     }
 
+    #[test]
+    fn as_lilypond_breve_and_longa() {
+        assert_eq!(Duration(RatioDuration(2, 1)).as_lilypond(), "\\breve");
+        assert_eq!(Duration(RatioDuration(4, 1)).as_lilypond(), "\\longa");
+        assert_eq!(Duration(RatioDuration(3, 1)).as_lilypond(), "\\breve.");
+        assert_eq!(Duration(RatioDuration(3, 2)).as_lilypond(), "1.");
+    }
+
+    #[test]
+    fn longest_printable() {
+        let longest = |num, den| RatioDuration(num, den).longest_printable().as_ratio();
+        assert_eq!(longest(3, 8), (3, 8));
+        assert_eq!(longest(5, 8), (1, 2));
+        assert_eq!(longest(7, 8), (3, 4));
+        assert_eq!(longest(5, 1), (4, 1));
+        assert_eq!(longest(15, 2), (6, 1));
+        assert_eq!(longest(1, 3), (1, 3));
+    }
+
+    #[test]
+    fn as_lilypond_in_tuplet() {
+        let dur = Duration(RatioDuration(1, 12));
+        assert_eq!(dur.as_lilypond_in_tuplet((3, 2)), "8");
+    }
+
+    #[test]
+    fn duration_from_lilypond() {
+        assert_eq!(RatioDuration::from_lilypond("4"), Ok(RatioDuration(1, 4)));
+        assert_eq!(RatioDuration::from_lilypond("4."), Ok(RatioDuration(3, 8)));
+        assert_eq!(RatioDuration::from_lilypond("2.."), Ok(RatioDuration(7, 8)));
+        assert!(RatioDuration::from_lilypond("3").is_err());
+
+        // Too many dots, or too short a value, can't be held rather than overflowing
+        let dots = format!("4{}", ".".repeat(32));
+        assert_eq!(RatioDuration::from_lilypond(&dots), Err(ParseError::new(&dots, "Duration is too short to hold")));
+        assert!(RatioDuration::from_lilypond("1073741824..").is_err());
+        assert!(RatioDuration::from_lilypond("2147483648").is_ok());
+    }
+
     #[test]
     fn test_serialize_duration() {
         let dur = Duration(RatioDuration(3, 4));
@@ -261,13 +673,45 @@ mod tests {
 
     #[test]
     fn test_tokens_integer_duration() {
-        let dur = IntegerDuration(1);
+        let dur = IntegerDuration::counting(1, 1);
         assert_tokens(&dur, &[
                       Token::NewtypeStruct { name: "IntegerDuration" },
                       Token::U32(1)
         ]);
     }
 
+    #[test]
+    fn test_integer_duration_in_quarters() {
+        let dur = Duration(IntegerDuration::counting(3, 4));
+        assert_eq!(dur.as_lilypond(), "4*3");
+        assert_eq!(dur.as_float(), 0.75);
+        assert_eq!(Duration::<IntegerDuration>::from(3).as_lilypond(), "1*3");
+        assert_tokens(&dur.0, &[
+                      Token::TupleStruct { name: "IntegerDuration", len: 2 },
+                      Token::U32(3),
+                      Token::U32(4),
+                      Token::TupleStructEnd
+        ]);
+        assert_eq!(serde_json::from_str::<IntegerDuration>("[3,4]").unwrap(), dur.0);
+        assert_eq!(serde_json::from_str::<IntegerDuration>("3").unwrap(), IntegerDuration::counting(3, 1));
+        assert!(serde_json::from_str::<IntegerDuration>("[3,6]").is_err());
+    }
+
+    #[test]
+    fn integer_duration_new_keeps_whole_notes() {
+        assert_eq!(Duration::<IntegerDuration>::new(6, 1), Duration(IntegerDuration::counting(6, 1)));
+        assert_eq!(Duration::<IntegerDuration>::new(3, 4), Duration(IntegerDuration::counting(3, 4)));
+        assert_eq!(Duration::<IntegerDuration>::new(6, 3), Duration(IntegerDuration::counting(2, 1)));
+        let quarters = Duration(IntegerDuration::counting(1, 4)) + Duration(IntegerDuration::counting(1, 4));
+        assert_eq!(quarters.as_lilypond(), "2*1");
+    }
+
+    #[test]
+    #[should_panic]
+    fn integer_duration_rejects_unit() {
+        Duration::<IntegerDuration>::new(1, 3);
+    }
+
     #[test]
     fn test_tokens_ratio_duration() {
         let dur = RatioDuration(1, 4);
@@ -281,7 +725,7 @@ mod tests {
 
     #[test]
     fn test_tokens_duration() {
-        let dur = Duration(IntegerDuration(1));
+        let dur = Duration(IntegerDuration::counting(1, 1));
         assert_tokens(&dur, &[
                       Token::NewtypeStruct { name: "Duration" },
                       Token::NewtypeStruct { name: "IntegerDuration" },
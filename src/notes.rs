@@ -2,30 +2,395 @@
 //! point in time that passes through the program will need to implement `Note` in some form, while
 //! `Pitch` is specific to translating the onset of the `Note` into text.
 
-use super::{Duration, Durational, Pitch};
-use serde::{Serialize, Serializer};
+use super::{Duration, Durational, Pitch, ParseError};
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
 use serde::ser::SerializeStruct;
+use serde::de::{self, Visitor, MapAccess, IgnoredAny};
+use std::fmt;
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
 
-pub trait Note
+pub trait Note<D>
+where D: Durational
 {
     /// Text of the beginning of the note (excluding duration), which will be passed on to the
     /// given template. This appears at the start of the Note and is repeated as necessary
     /// following any ties.
     fn text(&self) -> String;
 
+    /// The sounding duration of the note, which is what gets consumed from any `Grouping`.
+    fn duration(&self) -> Duration<D>;
+
+    /// Replaces the sounding duration of the note. Used by operations (such as quantization)
+    /// that rewrite the rhythm of a passage in place.
+    fn set_duration(&mut self, duration: Duration<D>);
+
+    /// The note value that is printed, which is usually the same as the sounding `duration()`.
+    /// The two differ in cases like proportional notation, where the printed note value is only
+    /// a visual indication. Timing always follows the sounding duration.
+    fn notated_duration(&self) -> Duration<D> {
+        self.duration()
+    }
+
     /// Annotation text that will be printed above the initial note onset, but not at any later
     /// points.
-    fn annotations(&self) -> &str {
-        &""
+    fn annotations(&self) -> String {
+        String::new()
+    }
+
+    /// Whether the note is tied, regardless of where it falls within any `Grouping`.
+    fn tie(&self) -> TiePolicy {
+        TiePolicy::Auto
+    }
+
+    /// Lilypond commands written before the note, such as a stem direction.
+    fn prefix(&self) -> String {
+        String::new()
+    }
+
+    /// Lilypond commands written after the note (and any ties within it), which undo those in
+    /// `prefix()` so that they only apply to this one note.
+    fn suffix(&self) -> String {
+        String::new()
+    }
+
+    /// Whether the note is an ornament taking no time of its own. A grace note has a zero
+    /// `duration()`, and is written with its `notated_duration()`.
+    fn is_grace(&self) -> bool {
+        false
+    }
+
+    /// Whether the note is silent. Rests can't be tied, so a rest split across a `Grouping` is
+    /// written as several rests, one after another.
+    fn is_rest(&self) -> bool {
+        false
+    }
+}
+
+/// Text written above or below a note with Lilypond's `\\markup`, such as an expression marking.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Markup {
+    text: String,
+    bold: bool,
+    italic: bool,
+    placement: Placement
+}
+
+/// Which side of the staff a `Markup` is placed on.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Placement {
+    Above,
+    Below
+}
+
+impl Markup {
+    /// Plain text placed above the staff.
+    pub fn new(text: &str) -> Self {
+        Markup {
+            text: text.to_string(),
+            bold: false,
+            italic: false,
+            placement: Placement::Above
+        }
+    }
+
+    pub fn bold(mut self) -> Self {
+        self.bold = true;
+        self
+    }
+
+    pub fn italic(mut self) -> Self {
+        self.italic = true;
+        self
+    }
+
+    pub fn above(mut self) -> Self {
+        self.placement = Placement::Above;
+        self
+    }
+
+    pub fn below(mut self) -> Self {
+        self.placement = Placement::Below;
+        self
+    }
+
+    pub fn as_lilypond(&self) -> String {
+        format!("{}{}", self.placement.as_lilypond(), self.as_markup())
+    }
+
+    /// The `\\markup` block alone, without the direction it is placed in, for use inside of
+    /// another command.
+    pub fn as_markup(&self) -> String {
+        let bold = if self.bold { "\\bold " } else { "" };
+        let italic = if self.italic { "\\italic " } else { "" };
+        format!("\\markup {{ {}{}{} }}", bold, italic, self.text)
+    }
+
+    pub fn placement(&self) -> Placement {
+        self.placement
+    }
+}
+
+impl Placement {
+    /// The Lilypond direction indicator placing a post-event on this side of the staff.
+    pub fn as_lilypond(&self) -> &'static str {
+        match *self {
+            Placement::Above => "^",
+            Placement::Below => "_"
+        }
+    }
+}
+
+/// A mark telling how a note is attacked or held, written after the note's duration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Articulation {
+    Staccato,
+    Staccatissimo,
+    Tenuto,
+    Accent,
+    Marcato,
+    Portato
+}
+
+impl Articulation {
+    pub fn as_lilypond(&self) -> &'static str {
+        match *self {
+            Articulation::Staccato => "-.",
+            Articulation::Staccatissimo => "-!",
+            Articulation::Tenuto => "--",
+            Articulation::Accent => "->",
+            Articulation::Marcato => "-^",
+            Articulation::Portato => "-_"
+        }
     }
 }
 
+/// A dynamic marking, written after the note or chord it begins on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Dynamic {
+    Ppp,
+    Pp,
+    P,
+    Mp,
+    Mf,
+    F,
+    Ff,
+    Fff,
+    Fp,
+    Sf,
+    Sfz
+}
+
+impl Dynamic {
+    pub fn as_lilypond(&self) -> &'static str {
+        match *self {
+            Dynamic::Ppp => "\\ppp",
+            Dynamic::Pp => "\\pp",
+            Dynamic::P => "\\p",
+            Dynamic::Mp => "\\mp",
+            Dynamic::Mf => "\\mf",
+            Dynamic::F => "\\f",
+            Dynamic::Ff => "\\ff",
+            Dynamic::Fff => "\\fff",
+            Dynamic::Fp => "\\fp",
+            Dynamic::Sf => "\\sf",
+            Dynamic::Sfz => "\\sfz"
+        }
+    }
+}
+
+/// Forces the direction of the stems of a note. Lilypond's stem commands stay in effect until they
+/// are changed, so `Up` and `Down` are followed by `\stemNeutral` once the note is over, and
+/// `Neutral` simply resets the direction before the note.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum StemDirection {
+    Up,
+    Down,
+    Neutral
+}
+
+impl StemDirection {
+    fn prefix(&self) -> String {
+        match *self {
+            StemDirection::Up => "\\stemUp ",
+            StemDirection::Down => "\\stemDown ",
+            StemDirection::Neutral => "\\stemNeutral "
+        }.to_string()
+    }
+
+    fn suffix(&self) -> String {
+        match *self {
+            StemDirection::Up | StemDirection::Down => " \\stemNeutral",
+            StemDirection::Neutral => ""
+        }.to_string()
+    }
+}
+
+/// Overrides the ties that `format_note` writes for a note.
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub enum TiePolicy {
+    /// Ties the pieces of a note that is split across `Grouping` boundaries.
+    #[default]
+    Auto,
+    /// Also ties the end of the note into the note that follows.
+    Force,
+    /// Never splits the note, and fails to format it if it overflows a `Grouping`.
+    Never
+}
+
 /// On the incomprehensible reason you would want to use equal temperament, this quicky is provided
 /// to translate midi note values into easy chord names.
-#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+///
+/// Two `ETPitch`es are equal, and ordered, by their MIDI note and cents offset alone, regardless
+/// of how they are spelled or which language their names are written in.
+#[derive(Clone, Copy, Debug)]
 pub struct ETPitch
 {
-    pub midi: u32
+    pub midi: u32,
+    /// Letter name and alteration the pitch is written with. When `None`, C#, Eb, F#, G# and Bb
+    /// are used for the black keys.
+    pub spelling: Option<Spelling>,
+    pub language: NoteLanguage,
+    /// Offset from the equal-tempered `midi` note, in cents. An offset nearer to 50 cents than to
+    /// 0 is written as a quarter-tone accidental.
+    pub cents: i32
+}
+
+/// The letter name of a pitch (`'c'` through `'b'`) and the number of semitones it is raised by
+/// accidentals, which is negative for flats.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Spelling {
+    pub letter: char,
+    pub alteration: i32
+}
+
+/// The letters in the order their sharps are added to a key signature, which is also their order
+/// along the line of fifths.
+static FIFTHS: [char; 7] = ['f', 'c', 'g', 'd', 'a', 'e', 'b'];
+
+impl Spelling {
+    /// Position of the spelling along the line of fifths, counting from C, so that G is 1, F is
+    /// -1 and F-sharp is 6.
+    fn fifths(&self) -> i32 {
+        let position = FIFTHS.iter().position(|&letter| letter == self.letter).unwrap_or(1) as i32;
+        position - 1 + 7 * self.alteration
+    }
+
+    /// The spelling at a position along the line of fifths, counting from C.
+    fn from_fifths(fifths: i32) -> Self {
+        Spelling {
+            letter: FIFTHS[(fifths + 1).rem_euclid(7) as usize],
+            alteration: (fifths + 1).div_euclid(7)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Major,
+    Minor
+}
+
+/// A key, given by its tonic and mode, such as F-sharp major.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeySignature {
+    pub tonic: Spelling,
+    pub mode: Mode
+}
+
+impl KeySignature {
+    pub fn major(letter: char, alteration: i32) -> Self {
+        KeySignature { tonic: Spelling { letter, alteration }, mode: Mode::Major }
+    }
+
+    pub fn minor(letter: char, alteration: i32) -> Self {
+        KeySignature { tonic: Spelling { letter, alteration }, mode: Mode::Minor }
+    }
+
+    /// Number of sharps in the key signature, which is negative for flats.
+    pub fn sharps(&self) -> i32 {
+        match self.mode {
+            Mode::Major => self.tonic.fifths(),
+            Mode::Minor => self.tonic.fifths() - 3
+        }
+    }
+
+    /// The alteration the key signature gives a letter, such as 1 for F in G major.
+    pub fn alteration(&self, letter: char) -> i32 {
+        let position = FIFTHS.iter().position(|&other| other == letter).unwrap_or(1) as i32;
+        (self.sharps() + 6 - position).div_euclid(7)
+    }
+
+    /// Spells a MIDI note in the key. Notes of the key are spelled as the key signature has them,
+    /// so F is E-sharp in F-sharp major, and the leading tone of a minor key is raised, as G-sharp
+    /// is in A minor. Any other note takes the spelling nearest to the tonic along the line of
+    /// fifths, so that D is D natural in F-sharp major and B-flat is not A-sharp in D major.
+    pub fn spell(&self, midi: u32) -> Spelling {
+        let tonic = self.tonic.fifths();
+        let class = (midi % 12) as i32;
+        // Seven fifths make a semitone, so a note `q` fifths above C has the pitch class `7 * q`.
+        // The leading tone lies five fifths above the tonic.
+        if self.mode == Mode::Minor && (7 * (tonic + 5)).rem_euclid(12) == class {
+            return Spelling::from_fifths(tonic + 5);
+        }
+        // Twelve fifths around the tonic, from five below to six above, each of which spells a
+        // different pitch class
+        let lowest = tonic - 5;
+        let offset = (7 * class - lowest).rem_euclid(12);
+        Spelling::from_fifths(lowest + offset)
+    }
+
+    /// The Lilypond `\key` command for the key, with the tonic named in `language`.
+    pub fn as_lilypond(&self, language: NoteLanguage) -> String {
+        let mode = match self.mode {
+            Mode::Major => "\\major",
+            Mode::Minor => "\\minor"
+        };
+        format!("\\key {} {}", language.note_name(self.tonic), mode)
+    }
+}
+
+/// The note-name languages of Lilypond's `\language` command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum NoteLanguage {
+    /// English names with the accidentals written out, as in `csharp` and `eflat`.
+    #[default]
+    EnglishVerbose,
+    /// English names with short accidentals, as in `cs` and `ef`.
+    English,
+    /// Lilypond's default names, as in `cis` and `es`.
+    Nederlands,
+    /// As `Nederlands`, except that B is `h` and B-flat is `b`.
+    Deutsch,
+    /// Solfège names, as in `dod` and `mib`.
+    Italiano
+}
+
+impl PartialEq for ETPitch {
+    fn eq(&self, other: &Self) -> bool {
+        self.midi == other.midi && self.cents == other.cents
+    }
+}
+
+impl Eq for ETPitch {}
+
+impl Hash for ETPitch {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.midi.hash(state);
+        self.cents.hash(state);
+    }
+}
+
+impl PartialOrd for ETPitch {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ETPitch {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.midi, self.cents).cmp(&(other.midi, other.cents))
+    }
 }
 
 impl Serialize for ETPitch
@@ -33,24 +398,287 @@ impl Serialize for ETPitch
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> 
         where S: Serializer
     {
-        let mut s = serializer.serialize_struct("ETPitch", 2)?;
+        // The offset, spelling and language are only written for pitches that have their own
+        let cents = self.cents != 0;
+        let language = self.language != NoteLanguage::default();
+        let len = 2 + cents as usize + self.spelling.is_some() as usize + language as usize;
+        let mut s = serializer.serialize_struct("ETPitch", len)?;
         s.serialize_field("midi", &self.midi)?;
         s.serialize_field("ly", &self.pitch())?;
+        if cents {
+            s.serialize_field("cents", &self.cents)?;
+        }
+        if let Some(ref spelling) = self.spelling {
+            s.serialize_field("spelling", spelling)?;
+        }
+        if language {
+            s.serialize_field("language", &self.language)?;
+        }
         s.end()
     }
 }
 
-static ET_SCALE: [&str; 12] = ["c", "csharp", "d", "eflat", "e", "f", "fsharp", "g", "gsharp", "a", "bflat", "b"];
+/// Reads an `ETPitch` either in the serialized form, where the `midi` field is used along with
+/// any `cents`, `spelling` and `language` (the `ly` name is ignored), or from a bare Lilypond note
+/// name such as `"cs'"`.
+impl<'de> Deserialize<'de> for ETPitch
+{
+    fn deserialize<De>(deserializer: De) -> Result<Self, De::Error> 
+        where De: Deserializer<'de>
+    {
+        deserializer.deserialize_any(ETPitchVisitor)
+    }
+}
+
+struct ETPitchVisitor;
+
+impl<'de> Visitor<'de> for ETPitchVisitor {
+    type Value = ETPitch;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an ETPitch struct or a Lilypond note name")
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<ETPitch, E> 
+        where E: de::Error
+    {
+        ETPitch::from_lilypond(value).map_err(|e| E::custom(e.to_string()))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<ETPitch, A::Error> 
+        where A: MapAccess<'de>
+    {
+        let mut midi = None;
+        let mut cents = 0;
+        let mut spelling = None;
+        let mut language = NoteLanguage::default();
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "midi" => midi = Some(map.next_value()?),
+                "cents" => cents = map.next_value()?,
+                "spelling" => spelling = map.next_value()?,
+                "language" => language = map.next_value()?,
+                _ => { map.next_value::<IgnoredAny>()?; }
+            }
+        }
+        let midi = midi.ok_or_else(|| de::Error::missing_field("midi"))?;
+        let mut pitch = ETPitch::new(midi).with_cents(cents).in_language(language);
+        pitch.spelling = spelling;
+        Ok(pitch)
+    }
+}
+
+/// Letter name and alteration of each pitch class when the pitch has no `Spelling` of its own.
+static ET_SCALE: [(char, i32); 12] = [('c', 0), ('c', 1), ('d', 0), ('e', -1), ('e', 0), ('f', 0), 
+                                      ('f', 1), ('g', 0), ('g', 1), ('a', 0), ('b', -1), ('b', 0)];
 
 impl ETPitch {
     pub fn new(midi: u32) -> Self {
-        ETPitch { midi }
+        ETPitch { 
+            midi,
+            spelling: None,
+            language: NoteLanguage::default(),
+            cents: 0
+        }
+    }
+
+    /// Spells the pitch from a table of names for the twelve pitch classes starting from C, such
+    /// as one with `"dflat"` for C#, in place of the default spelling. The names are read as in
+    /// `from_lilypond`, and the pitch is still written in its own `language`. A pitch that already
+    /// has a `spelling` keeps it.
+    ///
+    /// Fails when a name in the table is not a pitch name, or names another pitch class.
+    pub fn with_scale(mut self, scale: &[&str; 12]) -> Result<Self, ParseError> {
+        let name = scale[self.midi as usize % 12];
+        let named = ETPitch::from_lilypond(name)?;
+        if named.midi % 12 != self.midi % 12 {
+            return Err(ParseError::new(name, "Name is not of the right pitch class"));
+        }
+        if self.spelling.is_none() {
+            self.spelling = named.spelling;
+        }
+        Ok(self)
+    }
+
+    /// Detunes the pitch by some number of cents from its equal-tempered `midi` note.
+    pub fn with_cents(mut self, cents: i32) -> Self {
+        self.cents = cents;
+        self
+    }
+
+    /// The offset in cents rounded to the nearest quarter tone: 1 for a quarter tone sharp, -1
+    /// for a quarter tone flat, and 0 for an offset closer to the `midi` note itself.
+    pub fn quarter_tones(&self) -> i32 {
+        (self.cents as f64 / 50.).round() as i32
+    }
+
+    /// Sets the language that `pitch()` writes the name of the pitch in.
+    pub fn in_language(mut self, language: NoteLanguage) -> Self {
+        self.language = language;
+        self
+    }
+
+    /// The spelling of the pitch, falling back to the default for its pitch class.
+    pub fn spelling(&self) -> Spelling {
+        self.spelling.unwrap_or_else(|| {
+            let (letter, alteration) = ET_SCALE[self.midi as usize % 12];
+            Spelling { letter, alteration }
+        })
+    }
+
+    /// The name of the pitch as `pitch()` writes it, but spelled to suit `key` (see
+    /// `KeySignature::spell`) in place of its own spelling.
+    pub fn spell_in_key(&self, key: &KeySignature) -> String {
+        ETPitch { spelling: Some(key.spell(self.midi)), ..*self }.pitch()
+    }
+
+    /// Reads a Lilypond pitch name in absolute octave, such as `c`, `fis'` or `bflat,`. Both the
+    /// default (Dutch) accidentals and the English ones are understood. Following `pitch()`, the
+    /// unmarked octave starts from MIDI note 60.
+    pub fn from_lilypond(text: &str) -> Result<ETPitch, ParseError> {
+        let mut chars = text.chars();
+        let letter = chars.next().ok_or_else(|| ParseError::new(text, "Empty pitch"))?;
+        let step: i32 = match letter {
+            'c' => 0, 'd' => 2, 'e' => 4, 'f' => 5, 'g' => 7, 'a' => 9, 'b' => 11,
+            _ => return Err(ParseError::new(text, "Unknown pitch name"))
+        };
+        let rest = chars.as_str();
+        let accidental = rest.trim_end_matches(['\'', ',']);
+        let alteration: i32 = match (letter, accidental) {
+            (_, "") => 0,
+            ('e', "s") | ('a', "s") => -1,
+            ('e', "ses") | ('a', "ses") => -2,
+            (_, "is") | (_, "s") | (_, "sharp") | (_, "-sharp") => 1,
+            (_, "es") | (_, "f") | (_, "flat") | (_, "-flat") => -1,
+            (_, "isis") | (_, "ss") | (_, "x") | (_, "sharpsharp") | (_, "-sharpsharp") => 2,
+            (_, "eses") | (_, "ff") | (_, "flatflat") | (_, "-flatflat") => -2,
+            _ => return Err(ParseError::new(text, "Unknown accidental"))
+        };
+        let mut octave = 0;
+        for mark in rest[accidental.len()..].chars() {
+            match mark {
+                '\'' => octave += 1,
+                ',' => octave -= 1,
+                _ => return Err(ParseError::new(text, "Unknown octave mark"))
+            }
+        }
+        let midi = 60 + step + alteration + 12 * octave;
+        if midi < 0 {
+            return Err(ParseError::new(text, "Pitch is below MIDI note 0"));
+        }
+        let mut pitch = ETPitch::new(midi as u32);
+        pitch.spelling = Some(Spelling { letter, alteration });
+        Ok(pitch)
+    }
+
+    /// Number of semitones from this pitch up to `other`, which is negative when `other` is lower.
+    pub fn interval_to(&self, other: &Self) -> i32 {
+        other.midi as i32 - self.midi as i32
+    }
+
+    /// Name of the pitch in ABC notation, spelled as in `pitch()`. Upper case letters are the
+    /// octave from middle C (MIDI note 60), lower case letters the octave above, and each `'` or
+    /// `,` moves a further octave up or down.
+    pub fn as_abc(&self) -> String {
+        let spelling = self.spelling();
+        let accidental = if spelling.alteration < 0 { "_" } else { "^" };
+        let accidental = accidental.repeat(spelling.alteration.unsigned_abs() as usize);
+        // The octave belongs to the letter, so that B# and Cb are written beside their letters
+        let octave = (self.midi as i32 - spelling.alteration).div_euclid(12) - 5;
+        if octave > 0 {
+            accidental + &spelling.letter.to_string() + &"'".repeat(octave as usize - 1)
+        } else {
+            accidental + &spelling.letter.to_ascii_uppercase().to_string() + &",".repeat(-octave as usize)
+        }
+    }
+}
+
+impl NoteLanguage {
+    /// The argument to Lilypond's `\language` command for this language.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            NoteLanguage::EnglishVerbose | NoteLanguage::English => "english",
+            NoteLanguage::Nederlands => "nederlands",
+            NoteLanguage::Deutsch => "deutsch",
+            NoteLanguage::Italiano => "italiano"
+        }
+    }
+
+    /// Writes the note name for a letter raised by some odd number of quarter tones, which is
+    /// negative for flats. Quarter tones up to a three-quarter sharp or flat have names of their
+    /// own; both kinds of English names use the short forms, such as `cqs` and `ctqf`.
+    pub fn quarter_tone_name(&self, letter: char, quarters: i32) -> String {
+        let half = match (*self, quarters.signum()) {
+            (NoteLanguage::EnglishVerbose, 1) | (NoteLanguage::English, 1) => "qs",
+            (NoteLanguage::EnglishVerbose, _) | (NoteLanguage::English, _) => "qf",
+            (NoteLanguage::Italiano, 1) => "sd",
+            (NoteLanguage::Italiano, _) => "sb",
+            (_, 1) => "ih",
+            (_, _) => "eh"
+        };
+        let semitones = quarters / 2;
+        match *self {
+            NoteLanguage::EnglishVerbose | NoteLanguage::English => {
+                let three = if semitones != 0 { "t" } else { "" };
+                letter.to_string() + three + half
+            },
+            // Everywhere else the quarter tone follows the name with the rest of the alteration,
+            // as in `cisih` and `dobsb`
+            _ => self.note_name(Spelling { letter, alteration: semitones }) + half
+        }
+    }
+
+    /// Writes the note name for a letter and an alteration in this language.
+    pub fn note_name(&self, spelling: Spelling) -> String {
+        let Spelling { letter, alteration } = spelling;
+        let count = alteration.unsigned_abs() as usize;
+        match (*self, letter, alteration) {
+            (NoteLanguage::EnglishVerbose, _, _) => {
+                let accidental = if alteration < 0 { "flat" } else { "sharp" };
+                letter.to_string() + &accidental.repeat(count)
+            },
+            (NoteLanguage::English, _, _) => {
+                let accidental = if alteration < 0 { "f" } else { "s" };
+                letter.to_string() + &accidental.repeat(count)
+            },
+            (NoteLanguage::Italiano, _, _) => {
+                let syllable = match letter {
+                    'c' => "do", 'd' => "re", 'e' => "mi", 'f' => "fa", 'g' => "sol", 'a' => "la", _ => "si"
+                };
+                let accidental = if alteration < 0 { "b" } else { "d" };
+                syllable.to_string() + &accidental.repeat(count)
+            },
+            (NoteLanguage::Deutsch, 'b', 0) => "h".to_string(),
+            (NoteLanguage::Deutsch, 'b', -1) => "b".to_string(),
+            (NoteLanguage::Deutsch, 'b', -2) => "heses".to_string(),
+            (NoteLanguage::Deutsch, 'b', a) if a > 0 => "h".to_string() + &"is".repeat(count),
+            (_, 'e', a) | (_, 'a', a) if a < 0 => letter.to_string() + "s" + &"es".repeat(count - 1),
+            (_, _, _) => {
+                let accidental = if alteration < 0 { "es" } else { "is" };
+                letter.to_string() + &accidental.repeat(count)
+            }
+        }
     }
 }
 
 impl Pitch for ETPitch {
+    /// The note name followed by its octave marks, where the unmarked octave starts from MIDI
+    /// note 60 and each `'` or `,` moves an octave up or down, so that MIDI note 72 is `c'`.
+    ///
+    /// A pitch a quarter tone away from its `midi` note is written with a half-sharp or half-flat
+    /// accidental, such as `cih` for C raised by 50 cents in Lilypond's default language.
     fn pitch(&self) -> String {
-        ET_SCALE[self.midi as usize % 12].to_string()
+        let spelling = self.spelling();
+        // As in `as_abc`, the octave belongs to the letter rather than the sounding pitch
+        let octave = (self.midi as i32 - spelling.alteration).div_euclid(12) - 5;
+        let marks = if octave < 0 { "," } else { "'" };
+        let quarters = 2 * spelling.alteration + self.quarter_tones();
+        let name = if quarters % 2 == 0 {
+            self.language.note_name(Spelling { letter: spelling.letter, alteration: quarters / 2 })
+        } else {
+            self.language.quarter_tone_name(spelling.letter, quarters)
+        };
+        name + &marks.repeat(octave.unsigned_abs() as usize)
     }
 
     fn pitch_type(&self) -> &'static str {
@@ -64,10 +692,134 @@ fn from(f: u32) -> ETPitch {
 }
 }
 
+/// An unpitched percussion instrument, written with its Lilypond `\drummode` abbreviation. Music
+/// made of these belongs on a `DrumStaff` (see `partitura::Clef::Percussion`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DrumPitch {
+    BassDrum,
+    Snare,
+    SideStick,
+    HiHat,
+    ClosedHiHat,
+    OpenHiHat,
+    PedalHiHat,
+    RideCymbal,
+    CrashCymbal,
+    HighTom,
+    LowTom,
+    FloorTom,
+    Cowbell,
+    Tambourine
+}
+
+impl Pitch for DrumPitch {
+    fn pitch(&self) -> String {
+        let name = match *self {
+            DrumPitch::BassDrum => "bd",
+            DrumPitch::Snare => "sn",
+            DrumPitch::SideStick => "ss",
+            DrumPitch::HiHat => "hh",
+            DrumPitch::ClosedHiHat => "hhc",
+            DrumPitch::OpenHiHat => "hho",
+            DrumPitch::PedalHiHat => "hhp",
+            DrumPitch::RideCymbal => "cymr",
+            DrumPitch::CrashCymbal => "cymc",
+            DrumPitch::HighTom => "tomh",
+            DrumPitch::LowTom => "toml",
+            DrumPitch::FloorTom => "tomfl",
+            DrumPitch::Cowbell => "cb",
+            DrumPitch::Tambourine => "tamb"
+        };
+        name.to_string()
+    }
+
+    fn pitch_type(&self) -> &'static str {
+        "DrumPitch"
+    }
+}
+
+/// The note head of a pitchless note, for rhythms written on a single-line `RhythmicStaff` (see
+/// `partitura::Clef::Rhythmic`), where every note is drawn on the line whatever its pitch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Unpitched;
+
+/// A note of a rhythm, with a duration but no pitch.
+pub type RhythmNote<D> = SingleNote<Unpitched, D>;
+
+impl Pitch for Unpitched {
+    fn pitch(&self) -> String {
+        "c".to_string()
+    }
+
+    fn pitch_type(&self) -> &'static str {
+        "Unpitched"
+    }
+}
+
+/// Any of the kinds of `Pitch` in this crate, so that different kinds can be kept together (as in
+/// a `Vec<DynPitch>`) and still be serialized, which a `Box<dyn Pitch>` cannot. The pitch is
+/// serialized under `pitch`, tagged with its `pitch_type()`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "pitch_type", content = "pitch")]
+pub enum DynPitch {
+    ETPitch(ETPitch),
+    DrumPitch(DrumPitch)
+}
+
+impl Pitch for DynPitch {
+    fn pitch(&self) -> String {
+        match *self {
+            DynPitch::ETPitch(ref pitch) => pitch.pitch(),
+            DynPitch::DrumPitch(ref pitch) => pitch.pitch()
+        }
+    }
+
+    fn pitch_type(&self) -> &'static str {
+        match *self {
+            DynPitch::ETPitch(ref pitch) => pitch.pitch_type(),
+            DynPitch::DrumPitch(ref pitch) => pitch.pitch_type()
+        }
+    }
+}
+
+impl From<ETPitch> for DynPitch {
+    fn from(pitch: ETPitch) -> Self {
+        DynPitch::ETPitch(pitch)
+    }
+}
+
+impl From<DrumPitch> for DynPitch {
+    fn from(pitch: DrumPitch) -> Self {
+        DynPitch::DrumPitch(pitch)
+    }
+}
+
+/// Two `SingleNote`s are equal when every part of them is: the sounding and notated durations,
+/// the pitch, and all of the marks written on the note, such as articulations and fingering.
+/// `same_pitch_and_duration` compares only the first two.
 #[derive(Clone, Debug, PartialEq, Deserialize)]
 pub struct SingleNote<P: Pitch, D: Durational> {
     duration: Duration<D>,
-    pitch: P 
+    pitch: P,
+    notated_duration: Option<Duration<D>>,
+    #[serde(default)]
+    tie: TiePolicy,
+    #[serde(default)]
+    fingering: Option<u8>,
+    #[serde(default)]
+    stem: Option<StemDirection>,
+    #[serde(default)]
+    markup: Option<Markup>,
+    #[serde(default)]
+    glissando: bool,
+    #[serde(default)]
+    grace: bool,
+    #[serde(default)]
+    annotation: Option<String>,
+    #[serde(default)]
+    articulations: Vec<Articulation>,
+    #[serde(default)]
+    dynamic: Option<Dynamic>
 }
 
 impl<P, D> SingleNote<P, D> 
@@ -77,18 +829,146 @@ where P: Pitch,
     pub fn new<IntoP: Into<P>, T: Into<Duration<D>>>(pitch: IntoP, duration: T) -> Self {
         Self {
             duration: duration.into(),
-            pitch: pitch.into()
+            pitch: pitch.into(),
+            notated_duration: None,
+            tie: TiePolicy::Auto,
+            fingering: None,
+            stem: None,
+            markup: None,
+            glissando: false,
+            grace: false,
+            annotation: None,
+            articulations: Vec::new(),
+            dynamic: None
         }
     }
+
+    /// Creates a grace note (`\grace { }`), which is written with the given note value but
+    /// takes no time away from the notes around it.
+    pub fn grace<IntoP: Into<P>, T: Into<Duration<D>>>(pitch: IntoP, written: T) -> Self {
+        let mut note = Self::new(pitch, Duration::new(0, 1)).notated_as(written);
+        note.grace = true;
+        note
+    }
+
+    /// Prints the note with a different note value than it sounds for.
+    pub fn notated_as<T: Into<Duration<D>>>(mut self, notated_duration: T) -> Self {
+        self.notated_duration = Some(notated_duration.into());
+        self
+    }
+
+    pub fn pitch(&self) -> &P {
+        &self.pitch
+    }
+
+    /// Whether the two notes sound the same pitch for the same length of time, ignoring how
+    /// they are written and anything marked on them.
+    pub fn same_pitch_and_duration(&self, other: &Self) -> bool 
+    where P: PartialEq
+    {
+        self.pitch == other.pitch && self.duration == other.duration
+    }
+
+    /// Overrides how the note is tied.
+    pub fn with_tie(mut self, tie: TiePolicy) -> Self {
+        self.tie = tie;
+        self
+    }
+
+    /// Marks the note with the finger to play it with, written as `-3` after the dynamic and
+    /// articulations.
+    pub fn with_fingering(mut self, finger: u8) -> Self {
+        self.fingering = Some(finger);
+        self
+    }
+
+    /// Forces the direction of the note's stem.
+    pub fn with_stem(mut self, stem: StemDirection) -> Self {
+        self.stem = Some(stem);
+        self
+    }
+
+    /// Adds an articulation, written after the dynamic. Articulations are written in the order
+    /// they are added.
+    pub fn with_articulation(mut self, articulation: Articulation) -> Self {
+        self.articulations.push(articulation);
+        self
+    }
+
+    /// Marks the note with a dynamic, written straight after the duration.
+    pub fn with_dynamic(mut self, dynamic: Dynamic) -> Self {
+        self.dynamic = Some(dynamic);
+        self
+    }
+
+    /// Attaches text to the note, written after its duration, articulations and fingering.
+    pub fn with_markup(mut self, markup: Markup) -> Self {
+        self.markup = Some(markup);
+        self
+    }
+
+    /// Attaches any other Lilypond post-event to the note, such as `^"pizz."`. It is written
+    /// after the duration, following the articulations, fingering and markup.
+    pub fn with_annotation(mut self, annotation: &str) -> Self {
+        self.annotation = Some(annotation.to_string());
+        self
+    }
+
+    /// Slides from this note to the next one (`\glissando`). When the note is tied, the slide
+    /// starts from the last of its tied segments.
+    pub fn with_glissando(mut self) -> Self {
+        self.glissando = true;
+        self
+    }
 }
 
-impl<P, D> Note for SingleNote<P, D> 
+impl<P, D> Note<D> for SingleNote<P, D> 
 where P: Pitch,
       D: Durational
 {
     fn text(&self) -> String {
         self.pitch.pitch()
     }
+
+    fn duration(&self) -> Duration<D> {
+        self.duration
+    }
+
+    fn set_duration(&mut self, duration: Duration<D>) {
+        self.duration = duration;
+    }
+
+    fn notated_duration(&self) -> Duration<D> {
+        self.notated_duration.unwrap_or(self.duration)
+    }
+
+    fn tie(&self) -> TiePolicy {
+        self.tie
+    }
+
+    fn annotations(&self) -> String {
+        let fingering = self.fingering.map(|finger| format!("-{}", finger)).unwrap_or_default();
+        let markup = self.markup.as_ref().map(|markup| markup.as_lilypond()).unwrap_or_default();
+        let dynamic = self.dynamic.map(|dynamic| dynamic.as_lilypond()).unwrap_or("");
+        let articulations: String = self.articulations.iter().map(|articulation| articulation.as_lilypond()).collect();
+        let annotation = self.annotation.as_deref().unwrap_or("");
+        dynamic.to_string() + &articulations + &fingering + &markup + annotation
+    }
+
+    fn prefix(&self) -> String {
+        let grace = if self.grace { "\\grace { " } else { "" };
+        grace.to_string() + &self.stem.map(|stem| stem.prefix()).unwrap_or_default()
+    }
+
+    fn suffix(&self) -> String {
+        let glissando = if self.glissando { "\\glissando" } else { "" };
+        let grace = if self.grace { " }" } else { "" };
+        glissando.to_string() + &self.stem.map(|stem| stem.suffix()).unwrap_or_default() + grace
+    }
+
+    fn is_grace(&self) -> bool {
+        self.grace
+    }
 }
 
 impl<P, D> Serialize for SingleNote<P, D> 
@@ -98,13 +978,25 @@ where P: Pitch + Serialize,
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> 
         where S: Serializer
     {
-        let mut s = serializer.serialize_struct("SingleNote", 6)?;
+        let mut s = serializer.serialize_struct("SingleNote", 18)?;
         s.serialize_field("text", &self.text())?;
-        s.serialize_field("ly_duration", &self.duration.as_lilypond())?;
+        s.serialize_field("ly_duration", &self.notated_duration().as_lilypond())?;
         s.serialize_field("annotations", &self.annotations())?;
+        s.serialize_field("prefix", &self.prefix())?;
+        s.serialize_field("suffix", &self.suffix())?;
         s.serialize_field("pitch_type", &self.pitch.pitch_type())?;
         s.serialize_field("pitch", &self.pitch)?;
         s.serialize_field("duration", &self.duration)?;
+        s.serialize_field("notated_duration", &self.notated_duration)?;
+        s.serialize_field("tie", &self.tie)?;
+        s.serialize_field("fingering", &self.fingering)?;
+        s.serialize_field("stem", &self.stem)?;
+        s.serialize_field("markup", &self.markup)?;
+        s.serialize_field("glissando", &self.glissando)?;
+        s.serialize_field("grace", &self.grace)?;
+        s.serialize_field("annotation", &self.annotation)?;
+        s.serialize_field("articulations", &self.articulations)?;
+        s.serialize_field("dynamic", &self.dynamic)?;
         s.end()
     }
 }
@@ -115,41 +1007,214 @@ where P: Pitch,
       D: Durational
 {
     duration: Duration<D>,
-    pitches: Vec<P>
+    pitches: Vec<P>,
+    #[serde(default)]
+    tie: TiePolicy,
+    #[serde(default)]
+    arpeggio: bool,
+    #[serde(default)]
+    stem: Option<StemDirection>,
+    #[serde(default)]
+    articulations: Vec<Articulation>,
+    #[serde(default)]
+    dynamic: Option<Dynamic>
 }
 
 impl<P, D> Chord<P, D> 
 where P: Pitch,
       D: Durational
 {
+    /// Creates a chord of `pitches`, which are written in the order given. This keeps the
+    /// voicing of a chord as it was spelled out, such as with a doubled note below the root.
     pub fn new<U, T>(pitches: U, duration: T) -> Self 
         where U: Into<Vec<P>>,
               T: Into<Duration<D>>
     {
         Self {
             duration: duration.into(),
-            pitches: pitches.into()
+            pitches: pitches.into(),
+            tie: TiePolicy::Auto,
+            arpeggio: false,
+            stem: None,
+            articulations: Vec::new(),
+            dynamic: None
+        }
+    }
+
+    /// Overrides how the chord is tied.
+    pub fn with_tie(mut self, tie: TiePolicy) -> Self {
+        self.tie = tie;
+        self
+    }
+
+    /// Marks the chord to be rolled (`\arpeggio`).
+    pub fn arpeggiated(mut self) -> Self {
+        self.arpeggio = true;
+        self
+    }
+
+    /// Forces the direction of the chord's stem.
+    pub fn with_stem(mut self, stem: StemDirection) -> Self {
+        self.stem = Some(stem);
+        self
+    }
+
+    /// Adds an articulation to the whole chord, as in `SingleNote::with_articulation`.
+    pub fn with_articulation(mut self, articulation: Articulation) -> Self {
+        self.articulations.push(articulation);
+        self
+    }
+
+    /// Marks the chord with a dynamic, as in `SingleNote::with_dynamic`.
+    pub fn with_dynamic(mut self, dynamic: Dynamic) -> Self {
+        self.dynamic = Some(dynamic);
+        self
+    }
+}
+
+impl<P, D> Eq for Chord<P, D> 
+where P: Pitch + Eq,
+      D: Durational
+{}
+
+/// Chords are hashed by their pitches and duration, so that chords differing only in how they
+/// are tied or voiced with stems fall into the same bucket.
+impl<P, D> Hash for Chord<P, D> 
+where P: Pitch + Hash,
+      D: Durational
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.pitches.hash(state);
+        // Equal durations may be written in different terms, such as 1/2 and 2/4
+        self.duration.reduced().as_ratio().hash(state);
+    }
+}
+
+impl<D> Chord<ETPitch, D> 
+where D: Durational
+{
+    /// Builds a chord by stacking `intervals`, in semitones above `root`, on top of the root.
+    /// Each pitch takes the default spelling for its pitch class, in the language of the root.
+    pub fn from_intervals<T: Into<Duration<D>>>(root: ETPitch, intervals: &[u32], duration: T) -> Self {
+        let pitches: Vec<ETPitch> = Some(root).into_iter()
+            .chain(intervals.iter().map(|interval| ETPitch::new(root.midi + interval).in_language(root.language)))
+            .collect();
+        Chord::new(pitches, duration)
+    }
+
+    /// Reorders the pitches of the chord from lowest to highest. Pitches sharing the same
+    /// `midi` value keep the order they were given in.
+    pub fn sorted(mut self) -> Self {
+        self.pitches.sort_by_key(|pitch| pitch.midi);
+        self
+    }
+
+    /// Removes every pitch sounding the same `midi` note as an earlier one.
+    pub fn dedup_exact(mut self) -> Self {
+        let mut seen: Vec<u32> = Vec::with_capacity(self.pitches.len());
+        self.pitches.retain(|pitch| {
+            let is_new = !seen.contains(&pitch.midi);
+            seen.push(pitch.midi);
+            is_new
+        });
+        self
+    }
+
+    /// Collapses octave doublings, keeping only the lowest pitch of each pitch class. The pitches
+    /// that are kept stay in the order they were given.
+    pub fn dedup_pitch_classes(mut self) -> Self {
+        let mut lowest: Vec<Option<u32>> = vec![None; 12];
+        for pitch in self.pitches.iter() {
+            let class = (pitch.midi % 12) as usize;
+            lowest[class] = Some(lowest[class].map_or(pitch.midi, |midi| midi.min(pitch.midi)));
+        }
+        self.pitches.retain(|pitch| {
+            let class = (pitch.midi % 12) as usize;
+            let is_lowest = lowest[class] == Some(pitch.midi);
+            if is_lowest {
+                lowest[class] = None;
+            }
+            is_lowest
+        });
+        self
+    }
+
+    pub fn major<T: Into<Duration<D>>>(root: ETPitch, duration: T) -> Self {
+        Chord::from_intervals(root, &[4, 7], duration)
+    }
+
+    pub fn minor<T: Into<Duration<D>>>(root: ETPitch, duration: T) -> Self {
+        Chord::from_intervals(root, &[3, 7], duration)
+    }
+
+    pub fn diminished<T: Into<Duration<D>>>(root: ETPitch, duration: T) -> Self {
+        Chord::from_intervals(root, &[3, 6], duration)
+    }
+
+    pub fn augmented<T: Into<Duration<D>>>(root: ETPitch, duration: T) -> Self {
+        Chord::from_intervals(root, &[4, 8], duration)
+    }
+
+    pub fn major_seventh<T: Into<Duration<D>>>(root: ETPitch, duration: T) -> Self {
+        Chord::from_intervals(root, &[4, 7, 11], duration)
+    }
+
+    pub fn minor_seventh<T: Into<Duration<D>>>(root: ETPitch, duration: T) -> Self {
+        Chord::from_intervals(root, &[3, 7, 10], duration)
+    }
+
+    pub fn dominant_seventh<T: Into<Duration<D>>>(root: ETPitch, duration: T) -> Self {
+        Chord::from_intervals(root, &[4, 7, 10], duration)
+    }
+
+    /// Inverts the chord, moving its lowest pitch up an octave.
+    pub fn invert(mut self) -> Self {
+        let lowest = self.pitches.iter().enumerate()
+            .min_by_key(|&(_, pitch)| pitch.midi)
+            .map(|(index, _)| index);
+        if let Some(index) = lowest {
+            let pitch = self.pitches.remove(index);
+            self.pitches.push(ETPitch { midi: pitch.midi + 12, ..pitch });
         }
+        self
     }
 }
 
-impl<P, D> Note for Chord<P, D> 
+impl<P, D> Note<D> for Chord<P, D> 
 where P: Pitch,
       D: Durational
 {
     fn text(&self) -> String {
-        assert!(self.pitches.len() > 0);
-        let mut out = String::with_capacity(self.pitches.len() * 2 + 1);
-        out.push('<');
-        // Push all but the last character
-        for pitch in self.pitches[0..self.pitches.len()-1].iter() {
-            out.push_str(&pitch.pitch()[..]);
-            out.push(' ');
-        }
-        // Push the last character
-        out.push_str(&self.pitches[self.pitches.len()-1].pitch()[..]);
-        out.push('>');
-        out
+        assert!(!self.pitches.is_empty());
+        let names = self.pitches.iter().map(|p| p.pitch()).collect::<Vec<_>>();
+        format!("<{}>", names.join(" "))
+    }
+
+    fn duration(&self) -> Duration<D> {
+        self.duration
+    }
+
+    fn set_duration(&mut self, duration: Duration<D>) {
+        self.duration = duration;
+    }
+
+    fn tie(&self) -> TiePolicy {
+        self.tie
+    }
+
+    fn annotations(&self) -> String {
+        let arpeggio = if self.arpeggio { "\\arpeggio" } else { "" };
+        let dynamic = self.dynamic.map(|dynamic| dynamic.as_lilypond()).unwrap_or("");
+        let articulations: String = self.articulations.iter().map(|articulation| articulation.as_lilypond()).collect();
+        arpeggio.to_string() + dynamic + &articulations
+    }
+
+    fn prefix(&self) -> String {
+        self.stem.map(|stem| stem.prefix()).unwrap_or_default()
+    }
+
+    fn suffix(&self) -> String {
+        self.stem.map(|stem| stem.suffix()).unwrap_or_default()
     }
 }
 
@@ -160,22 +1225,184 @@ where P: Pitch + Serialize,
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> 
         where S: Serializer
     {
-        let mut s = serializer.serialize_struct("Chord", 6)?;
+        let mut s = serializer.serialize_struct("Chord", 13)?;
         s.serialize_field("text", &self.text())?;
         s.serialize_field("ly_duration", &self.duration.as_lilypond())?;
         s.serialize_field("annotations", &self.annotations())?;
+        s.serialize_field("prefix", &self.prefix())?;
+        s.serialize_field("suffix", &self.suffix())?;
         s.serialize_field("pitch_type", &self.pitches[0].pitch_type())?;
         s.serialize_field("pitches", &self.pitches)?;
         s.serialize_field("duration", &self.duration)?;
+        s.serialize_field("tie", &self.tie)?;
+        s.serialize_field("arpeggio", &self.arpeggio)?;
+        s.serialize_field("stem", &self.stem)?;
+        s.serialize_field("articulations", &self.articulations)?;
+        s.serialize_field("dynamic", &self.dynamic)?;
         s.end()
     }
 }
 
+/// A silence lasting for a given duration.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Rest<D> 
+where D: Durational
+{
+    duration: Duration<D>
+}
+
+impl<D> Rest<D> 
+where D: Durational
+{
+    pub fn new<T: Into<Duration<D>>>(duration: T) -> Self {
+        Self {
+            duration: duration.into()
+        }
+    }
+}
+
+impl<D> Note<D> for Rest<D> 
+where D: Durational
+{
+    fn text(&self) -> String {
+        "r".to_string()
+    }
+
+    fn duration(&self) -> Duration<D> {
+        self.duration
+    }
+
+    fn set_duration(&mut self, duration: Duration<D>) {
+        self.duration = duration;
+    }
+
+    fn is_rest(&self) -> bool {
+        true
+    }
+}
+
+impl<D> Serialize for Rest<D> 
+where D: Durational + Serialize
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> 
+        where S: Serializer
+    {
+        let mut s = serializer.serialize_struct("Rest", 4)?;
+        s.serialize_field("text", &self.text())?;
+        s.serialize_field("ly_duration", &self.duration.as_lilypond())?;
+        s.serialize_field("annotations", &self.annotations())?;
+        s.serialize_field("duration", &self.duration)?;
+        s.end()
+    }
+}
+
+/// Any one of the kinds of note, so that a single `Notes` can mix them. It is serialized as the
+/// note it holds, with an added `type` field naming the kind of note.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum NoteEvent<P, D> 
+where P: Pitch,
+      D: Durational
+{
+    SingleNote(SingleNote<P, D>),
+    Chord(Chord<P, D>),
+    Rest(Rest<D>)
+}
+
+impl<P, D> NoteEvent<P, D> 
+where P: Pitch,
+      D: Durational
+{
+    fn as_note(&self) -> &dyn Note<D> {
+        match *self {
+            NoteEvent::SingleNote(ref note) => note,
+            NoteEvent::Chord(ref chord) => chord,
+            NoteEvent::Rest(ref rest) => rest
+        }
+    }
+}
+
+impl<P, D> Note<D> for NoteEvent<P, D> 
+where P: Pitch,
+      D: Durational
+{
+    fn text(&self) -> String {
+        self.as_note().text()
+    }
+
+    fn duration(&self) -> Duration<D> {
+        self.as_note().duration()
+    }
+
+    fn set_duration(&mut self, duration: Duration<D>) {
+        match *self {
+            NoteEvent::SingleNote(ref mut note) => note.set_duration(duration),
+            NoteEvent::Chord(ref mut chord) => chord.set_duration(duration),
+            NoteEvent::Rest(ref mut rest) => rest.set_duration(duration)
+        }
+    }
+
+    fn notated_duration(&self) -> Duration<D> {
+        self.as_note().notated_duration()
+    }
+
+    fn annotations(&self) -> String {
+        self.as_note().annotations()
+    }
+
+    fn tie(&self) -> TiePolicy {
+        self.as_note().tie()
+    }
+
+    fn prefix(&self) -> String {
+        self.as_note().prefix()
+    }
+
+    fn suffix(&self) -> String {
+        self.as_note().suffix()
+    }
+
+    fn is_grace(&self) -> bool {
+        self.as_note().is_grace()
+    }
+
+    fn is_rest(&self) -> bool {
+        self.as_note().is_rest()
+    }
+}
+
+impl<P, D> From<SingleNote<P, D>> for NoteEvent<P, D> 
+where P: Pitch,
+      D: Durational
+{
+    fn from(note: SingleNote<P, D>) -> Self {
+        NoteEvent::SingleNote(note)
+    }
+}
+
+impl<P, D> From<Chord<P, D>> for NoteEvent<P, D> 
+where P: Pitch,
+      D: Durational
+{
+    fn from(chord: Chord<P, D>) -> Self {
+        NoteEvent::Chord(chord)
+    }
+}
+
+impl<P, D> From<Rest<D>> for NoteEvent<P, D> 
+where P: Pitch,
+      D: Durational
+{
+    fn from(rest: Rest<D>) -> Self {
+        NoteEvent::Rest(rest)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use super::super::{IntegerDuration};
-    use serde_test::{Token, assert_tokens};
+    use super::super::{IntegerDuration, RatioDuration};
+    use serde_test::{Token, assert_tokens, assert_de_tokens};
 
     #[test]
     fn translates_midi_to_note_name() {
@@ -183,6 +1410,280 @@ mod tests {
         assert_eq!(ETPitch::new(69).pitch(), "a");
     }
 
+    #[test]
+    fn deserialize_pitch_from_name() {
+        assert_de_tokens(&ETPitch::new(73), &[Token::Str("cs'")]);
+        let pitch: ETPitch = serde_json::from_str("\"bflat,\"").unwrap();
+        assert_eq!(pitch, ETPitch::new(58));
+        assert!(serde_json::from_str::<ETPitch>("\"h\"").is_err());
+    }
+
+    #[test]
+    fn deserialize_pitch_from_struct() {
+        let pitch: ETPitch = serde_json::from_str("{\"midi\": 62, \"ly\": \"d\"}").unwrap();
+        assert_eq!(pitch, ETPitch::new(62));
+        let pitch: ETPitch = serde_json::from_str("{\"midi\": 64}").unwrap();
+        assert_eq!(pitch, ETPitch::new(64));
+        assert!(serde_json::from_str::<ETPitch>("{\"ly\": \"d\"}").is_err());
+    }
+
+    #[test]
+    fn spell_in_sharp_keys() {
+        let f_sharp = KeySignature::major('f', 1);
+        assert_eq!(f_sharp.sharps(), 6);
+        assert_eq!(ETPitch::new(65).spell_in_key(&f_sharp), "esharp");
+        assert_eq!(ETPitch::new(70).spell_in_key(&f_sharp), "asharp");
+        assert_eq!(ETPitch::new(71).spell_in_key(&f_sharp), "b");
+        assert_eq!(f_sharp.as_lilypond(NoteLanguage::Nederlands), "\\key fis \\major");
+
+        // The leading tone of a minor key is raised
+        let a_minor = KeySignature::minor('a', 0);
+        assert_eq!(a_minor.sharps(), 0);
+        assert_eq!(ETPitch::new(68).spell_in_key(&a_minor), "gsharp");
+        assert_eq!(ETPitch::new(70).spell_in_key(&a_minor), "bflat");
+
+        let e_major = KeySignature::major('e', 0);
+        assert_eq!(ETPitch::new(63).spell_in_key(&e_major), "dsharp");
+        assert_eq!(ETPitch::new(70).spell_in_key(&e_major), "asharp");
+
+        // Notes outside of the key are spelled nearest to the tonic
+        assert_eq!(ETPitch::new(62).spell_in_key(&f_sharp), "d");
+        assert_eq!(ETPitch::new(67).spell_in_key(&f_sharp), "g");
+        assert_eq!(ETPitch::new(70).spell_in_key(&KeySignature::major('d', 0)), "bflat");
+        assert_eq!(ETPitch::new(65).spell_in_key(&KeySignature::major('a', 0)), "f");
+        assert_eq!(ETPitch::new(61).spell_in_key(&KeySignature::minor('d', 0)), "csharp");
+        assert_eq!(ETPitch::new(63).spell_in_key(&KeySignature::minor('e', 0)), "dsharp");
+    }
+
+    #[test]
+    fn spell_in_flat_keys() {
+        let e_flat = KeySignature::major('e', -1);
+        assert_eq!(e_flat.sharps(), -3);
+        assert_eq!(e_flat.alteration('a'), -1);
+        assert_eq!(e_flat.alteration('d'), 0);
+        assert_eq!(ETPitch::new(61).spell_in_key(&e_flat), "dflat");
+        assert_eq!(ETPitch::new(68).spell_in_key(&e_flat), "aflat");
+        assert_eq!(ETPitch::new(71).spell_in_key(&e_flat), "cflat'");
+        assert_eq!(ETPitch::new(66).spell_in_key(&e_flat), "gflat");
+        assert_eq!(ETPitch::new(64).spell_in_key(&KeySignature::major('b', -1)), "e");
+
+        let g_flat = KeySignature::major('g', -1);
+        assert_eq!(ETPitch::new(71).spell_in_key(&g_flat), "cflat'");
+        assert_eq!(ETPitch::new(66).in_language(NoteLanguage::Nederlands).spell_in_key(&g_flat), "ges");
+        let c_minor = KeySignature::minor('c', 0);
+        assert_eq!(c_minor.spell(63), Spelling { letter: 'e', alteration: -1 });
+        assert_eq!(c_minor.spell(71), Spelling { letter: 'b', alteration: 0 });
+        assert_eq!(c_minor.spell(68), Spelling { letter: 'a', alteration: -1 });
+    }
+
+    #[test]
+    fn pitch_names_from_scale() {
+        static FLATS: [&str; 12] = ["c", "dflat", "d", "eflat", "e", "f", "gflat", "g", "aflat", "a", "bflat", "b"];
+        let flat = |midi| ETPitch::new(midi).with_scale(&FLATS).unwrap();
+        assert_eq!(flat(61).pitch(), "dflat");
+        assert_eq!(flat(78).pitch(), "gflat'");
+        assert_eq!(flat(58).pitch(), "bflat,");
+        assert_eq!(flat(61).in_language(NoteLanguage::Nederlands).pitch(), "des");
+        // A spelling of the pitch's own comes before the table
+        assert_eq!(ETPitch::from_lilypond("cis").unwrap().with_scale(&FLATS).unwrap().pitch(), "csharp");
+        assert_eq!(flat(61), ETPitch::new(61));
+
+        // The octave follows the letter of the name, as it does for any other spelling
+        static SHARPS: [&str; 12] = ["bsharp", "csharp", "d", "dsharp", "e", "esharp", "fsharp", "g", "gsharp", "a", "asharp", "b"];
+        assert_eq!(ETPitch::new(60).with_scale(&SHARPS).unwrap().pitch(), "bsharp,");
+        assert_eq!(ETPitch::new(65).with_scale(&SHARPS).unwrap().pitch(), "esharp");
+
+        // The spelling is kept when the pitch is saved
+        let json = serde_json::to_value(flat(61)).unwrap();
+        assert_eq!(serde_json::from_value::<ETPitch>(json).unwrap().pitch(), "dflat");
+
+        let mut wrong = FLATS;
+        wrong[1] = "d";
+        assert!(ETPitch::new(61).with_scale(&wrong).is_err());
+        wrong[1] = "q";
+        assert!(ETPitch::new(61).with_scale(&wrong).is_err());
+    }
+
+    #[test]
+    fn pitch_names_in_language() {
+        let deutsch = |name: &str| ETPitch::from_lilypond(name).unwrap().in_language(NoteLanguage::Deutsch).pitch();
+        assert_eq!(ETPitch::new(61).in_language(NoteLanguage::Deutsch).pitch(), "cis");
+        assert_eq!(deutsch("des"), "des");
+        assert_eq!(deutsch("ees"), "es");
+        assert_eq!(deutsch("bes"), "b");
+        assert_eq!(deutsch("b"), "h");
+        assert_eq!(ETPitch::new(61).in_language(NoteLanguage::English).pitch(), "cs");
+        assert_eq!(ETPitch::new(70).in_language(NoteLanguage::Italiano).pitch(), "sib");
+        assert_eq!(ETPitch::from_lilypond("des").unwrap(), ETPitch::new(61));
+    }
+
+    #[test]
+    fn markup_placement() {
+        assert_eq!(Markup::new("dolce").italic().as_lilypond(), "^\\markup { \\italic dolce }");
+        assert_eq!(Markup::new("sub.").bold().italic().below().as_lilypond(), 
+                   "_\\markup { \\bold \\italic sub. }");
+    }
+
+    #[test]
+    fn chord_round_trip() {
+        let chord: Chord<ETPitch, RatioDuration> = Chord::new(vec![ETPitch::new(60), ETPitch::new(64)], 
+                                                              RatioDuration(1, 2))
+            .arpeggiated();
+        let json = serde_json::to_string(&chord).unwrap();
+        let read: Chord<ETPitch, RatioDuration> = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, chord);
+        assert_eq!(read.text(), "<c e>");
+        assert_eq!(serde_json::to_string(&read).unwrap(), json);
+    }
+
+    #[test]
+    fn invert_chord() {
+        let triad: Chord<ETPitch, RatioDuration> = Chord::new(vec![ETPitch::new(60), ETPitch::new(64), ETPitch::new(67)], 
+                                                              RatioDuration(1, 4));
+        let first = triad.invert();
+        assert_eq!(first.pitches, vec![ETPitch::new(64), ETPitch::new(67), ETPitch::new(72)]);
+        let second = first.invert();
+        assert_eq!(second.pitches, vec![ETPitch::new(67), ETPitch::new(72), ETPitch::new(76)]);
+
+        // The pitch moved up keeps its spelling and language
+        let pitches: Vec<ETPitch> = ["c", "ees", "g"].iter()
+            .map(|name| ETPitch::from_lilypond(name).unwrap().in_language(NoteLanguage::Deutsch))
+            .collect();
+        let minor: Chord<ETPitch, RatioDuration> = Chord::new(pitches, RatioDuration(1, 4));
+        assert_eq!(minor.text(), "<c es g>");
+        assert_eq!(minor.invert().invert().text(), "<g c' es'>");
+    }
+
+    #[test]
+    fn dyn_pitch_round_trip() {
+        let pitches: Vec<DynPitch> = vec![ETPitch::new(72).into(), DrumPitch::Snare.into()];
+        let json = serde_json::to_value(&pitches).unwrap();
+        assert_eq!(json, json!([
+            { "pitch_type": "ETPitch", "pitch": { "midi": 72, "ly": "c'" } },
+            { "pitch_type": "DrumPitch", "pitch": "Snare" }
+        ]));
+        let read: Vec<DynPitch> = serde_json::from_value(json).unwrap();
+        assert_eq!(read, pitches);
+        assert_eq!(read.iter().map(|pitch| pitch.pitch()).collect::<Vec<String>>(), vec!["c'", "sn"]);
+
+        let note: SingleNote<DynPitch, RatioDuration> = SingleNote::new(DrumPitch::BassDrum, RatioDuration(1, 4));
+        assert_eq!(note.text(), "bd");
+    }
+
+    #[test]
+    fn chord_qualities() {
+        let midi = |chord: Chord<ETPitch, RatioDuration>| chord.pitches.iter().map(|pitch| pitch.midi).collect::<Vec<u32>>();
+        assert_eq!(midi(Chord::major(ETPitch::new(60), RatioDuration(1, 4))), vec![60, 64, 67]);
+        assert_eq!(midi(Chord::minor(ETPitch::new(57), RatioDuration(1, 4))), vec![57, 60, 64]);
+        assert_eq!(midi(Chord::diminished(ETPitch::new(59), RatioDuration(1, 4))), vec![59, 62, 65]);
+        assert_eq!(midi(Chord::augmented(ETPitch::new(60), RatioDuration(1, 4))), vec![60, 64, 68]);
+        assert_eq!(midi(Chord::dominant_seventh(ETPitch::new(55), RatioDuration(1, 4))), vec![55, 59, 62, 65]);
+        assert_eq!(midi(Chord::major_seventh(ETPitch::new(60), RatioDuration(1, 4))), vec![60, 64, 67, 71]);
+        assert_eq!(midi(Chord::minor_seventh(ETPitch::new(62), RatioDuration(1, 4))), vec![62, 65, 69, 72]);
+
+        let chord: Chord<ETPitch, RatioDuration> = Chord::major(ETPitch::new(62).in_language(NoteLanguage::English), RatioDuration(1, 2));
+        assert_eq!(chord.text(), "<d fs a>");
+    }
+
+    #[test]
+    fn single_note_accessors() {
+        let note: SingleNote<ETPitch, RatioDuration> = SingleNote::new(67, RatioDuration(3, 8)).notated_as(RatioDuration(1, 4));
+        assert_eq!(note.pitch().midi, 67);
+        assert_eq!(note.duration(), Duration(RatioDuration(3, 8)));
+        assert_eq!(note.notated_duration(), Duration(RatioDuration(1, 4)));
+    }
+
+    #[test]
+    fn chord_dedup() {
+        let midi = |chord: Chord<ETPitch, RatioDuration>| chord.pitches.iter().map(|pitch| pitch.midi).collect::<Vec<u32>>();
+        let chord = |pitches: &[u32]| Chord::new(pitches.iter().map(|&midi| ETPitch::new(midi)).collect::<Vec<ETPitch>>(), RatioDuration(1, 4));
+        assert_eq!(midi(chord(&[60, 64, 67, 72]).dedup_pitch_classes()), vec![60, 64, 67]);
+        assert_eq!(midi(chord(&[76, 60, 64, 67, 48]).dedup_pitch_classes()), vec![64, 67, 48]);
+        assert_eq!(midi(chord(&[60, 64, 60, 72, 64]).dedup_exact()), vec![60, 64, 72]);
+    }
+
+    #[test]
+    fn hash_pitches_and_chords() {
+        use std::collections::HashSet;
+        let pitches: HashSet<ETPitch> = [60, 72, 60].iter().map(|&midi| ETPitch::new(midi)).collect();
+        assert_eq!(pitches.len(), 2);
+        // Spelling is ignored, as it is by `==`
+        assert!(pitches.contains(&ETPitch::from_lilypond("bs").unwrap()));
+
+        let mut chords: HashSet<Chord<ETPitch, RatioDuration>> = HashSet::new();
+        chords.insert(Chord::major(ETPitch::new(60), RatioDuration(1, 4)));
+        chords.insert(Chord::major(ETPitch::new(60), RatioDuration(1, 4)));
+        chords.insert(Chord::major(ETPitch::new(60), RatioDuration(1, 2)));
+        chords.insert(Chord::minor(ETPitch::new(60), RatioDuration(1, 4)));
+        assert_eq!(chords.len(), 3);
+    }
+
+    #[test]
+    fn quarter_tones() {
+        let c = ETPitch::new(60).with_cents(50);
+        assert_eq!(c.in_language(NoteLanguage::Nederlands).pitch(), "cih");
+        assert_eq!(c.pitch(), "cqs");
+        assert_eq!(ETPitch::new(60).with_cents(20).pitch(), "c");
+        assert_eq!(ETPitch::new(64).with_cents(-48).in_language(NoteLanguage::Nederlands).pitch(), "eeh");
+        assert_eq!(ETPitch::new(63).with_cents(-50).in_language(NoteLanguage::Nederlands).pitch(), "eseh");
+        assert_eq!(ETPitch::new(61).with_cents(50).in_language(NoteLanguage::Italiano).pitch(), "dodsd");
+        assert_eq!(ETPitch::new(71).with_cents(-50).in_language(NoteLanguage::Deutsch).pitch(), "heh");
+        assert_eq!(ETPitch::new(73).with_cents(50).in_language(NoteLanguage::English).pitch(), "ctqs'");
+        assert_ne!(c, ETPitch::new(60));
+
+        let json = serde_json::to_string(&c).unwrap();
+        assert_eq!(serde_json::from_str::<ETPitch>(&json).unwrap(), c);
+    }
+
+    #[test]
+    fn note_equality() {
+        let plain: SingleNote<ETPitch, RatioDuration> = SingleNote::new(60, RatioDuration(1, 4));
+        let staccato = plain.clone().with_articulation(Articulation::Staccato);
+        assert_ne!(plain, staccato);
+        assert!(plain.same_pitch_and_duration(&staccato));
+        assert_eq!(staccato.clone().with_articulation(Articulation::Accent).annotations(), "-.->");
+
+        assert!(plain.same_pitch_and_duration(&SingleNote::new(60, RatioDuration(2, 8))));
+        assert!(!plain.same_pitch_and_duration(&SingleNote::new(62, RatioDuration(1, 4))));
+        assert!(!plain.same_pitch_and_duration(&SingleNote::new(60, RatioDuration(1, 2))));
+    }
+
+    #[test]
+    fn chord_sorted() {
+        let chord: Chord<ETPitch, RatioDuration> = Chord::new(vec![ETPitch::new(67), ETPitch::new(60), ETPitch::new(64)], RatioDuration(1, 4));
+        assert_eq!(chord.text(), "<g c e>");
+        assert_eq!(chord.sorted().text(), "<c e g>");
+    }
+
+    #[test]
+    fn pitch_intervals() {
+        let c = ETPitch::new(60);
+        assert_eq!(c.interval_to(&ETPitch::new(60)), 0);
+        assert_eq!(c.interval_to(&ETPitch::new(72)), 12);
+        assert_eq!(c.interval_to(&ETPitch::new(55)), -5);
+        assert!(ETPitch::new(72) > c);
+        assert_eq!([ETPitch::new(64), c].iter().max(), Some(&ETPitch::new(64)));
+    }
+
+    #[test]
+    fn abc_pitch_names() {
+        assert_eq!(ETPitch::new(60).as_abc(), "C");
+        assert_eq!(ETPitch::new(72).as_abc(), "c");
+        assert_eq!(ETPitch::new(85).as_abc(), "^c'");
+        assert_eq!(ETPitch::new(58).as_abc(), "_B,");
+    }
+
+    #[test]
+    fn reads_lilypond_pitch_names() {
+        assert_eq!(ETPitch::from_lilypond("c").unwrap().midi, 60);
+        assert_eq!(ETPitch::from_lilypond("fis").unwrap().midi, 66);
+        assert_eq!(ETPitch::from_lilypond("bflat").unwrap().midi, 70);
+        assert_eq!(ETPitch::from_lilypond("es").unwrap().midi, 63);
+        assert_eq!(ETPitch::from_lilypond("cs'").unwrap().midi, 73);
+        assert_eq!(ETPitch::from_lilypond("g,,").unwrap().midi, 43);
+        assert!(ETPitch::from_lilypond("h").is_err());
+    }
+
     #[test]
     fn gets_single_note_name() {
         let note = SingleNote::<ETPitch, IntegerDuration>::new(ETPitch::new(62), 1);
@@ -201,11 +1702,27 @@ mod tests {
         assert_eq!(chord.text().as_str(), "<c>");
     }
 
+    #[test]
+    fn chord_names_by_size() {
+        let pitches = [ETPitch::new(60), ETPitch::new(64), ETPitch::new(67)];
+        let expected = ["<c>", "<c e>", "<c e g>"];
+        for (size, text) in (1..4).zip(expected.iter()) {
+            let chord = Chord::<ETPitch, IntegerDuration>::new(&pitches[0..size], 1);
+            assert_eq!(chord.text().as_str(), *text);
+        }
+    }
+
     #[test]
     #[should_panic]
     fn panic_on_empty_chord() {
         let chord = Chord::<ETPitch, IntegerDuration>::new(vec![], 1);
-        chord.text().as_str();
+        chord.text();
+    }
+
+    #[test]
+    fn gets_rest_name() {
+        let rest = Rest::<IntegerDuration>::new(1);
+        assert_eq!(rest.text().as_str(), "r");
     }
 
     #[test]
@@ -224,11 +1741,50 @@ mod tests {
         ]);
     }
 
+    #[test]
+    fn test_tokens_et_pitch_octaves() {
+        assert_tokens(&ETPitch::new(72), &[
+                      Token::Struct { name: "ETPitch", len: 2 },
+                      Token::Str("midi"),
+                      Token::U32(72),
+                      Token::Str("ly"),
+                      Token::Str("c'"),
+                      Token::StructEnd,
+        ]);
+
+        // The octave follows the letter, so B# sits below the C it sounds as
+        let pitches = ["b,", "bflat,", "c", "bsharp", "cflat'", "fsharp''"];
+        for ly in pitches.iter() {
+            let pitch = ETPitch::from_lilypond(ly).unwrap();
+            let json = serde_json::to_value(SingleNote::<ETPitch, RatioDuration>::new(pitch, RatioDuration(1, 4))).unwrap();
+            assert_eq!(json["text"], json!(ly));
+            assert_eq!(json["pitch"]["ly"], json!(ly));
+        }
+        assert_eq!(ETPitch::new(59).pitch(), "b,");
+        assert_eq!(ETPitch::new(36).pitch(), "c,,");
+    }
+
+    #[test]
+    fn et_pitch_round_trip() {
+        let pitch = ETPitch::from_lilypond("des'").unwrap().in_language(NoteLanguage::Deutsch).with_cents(-10);
+        let json = serde_json::to_value(pitch).unwrap();
+        assert_eq!(json["spelling"], json!({ "letter": "d", "alteration": -1 }));
+        assert_eq!(json["language"], json!("Deutsch"));
+        let read: ETPitch = serde_json::from_value(json).unwrap();
+        assert_eq!(read.spelling, pitch.spelling);
+        assert_eq!(read.language, NoteLanguage::Deutsch);
+        assert_eq!(read.pitch(), "des'");
+
+        // Pitches saved before the spelling and language were written take the defaults
+        let read: ETPitch = serde_json::from_value(json!({ "midi": 61, "ly": "des" })).unwrap();
+        assert_eq!(read.pitch(), "csharp");
+    }
+
     #[test]
     fn test_tokens_single_note() {
         let note = SingleNote::<ETPitch, IntegerDuration>::new(ETPitch::new(62), 1);
         assert_tokens(&note, &[
-                      Token::Struct { name: "SingleNote", len: 6 },
+                      Token::Struct { name: "SingleNote", len: 18 },
                       Token::Str("text"),
                       Token::Str("d"),
 
@@ -238,6 +1794,12 @@ mod tests {
                       Token::Str("annotations"),
                       Token::Str(""),
 
+                      Token::Str("prefix"),
+                      Token::Str(""),
+
+                      Token::Str("suffix"),
+                      Token::Str(""),
+
                       Token::Str("pitch_type"),
                       Token::Str("ETPitch"),
 
@@ -257,6 +1819,37 @@ mod tests {
                       Token::NewtypeStruct { name: "IntegerDuration" },
                       Token::U32(1),
 
+                      Token::Str("notated_duration"),
+                      Token::None,
+
+                      Token::Str("tie"),
+                      Token::UnitVariant { name: "TiePolicy", variant: "Auto" },
+
+                      Token::Str("fingering"),
+                      Token::None,
+
+                      Token::Str("stem"),
+                      Token::None,
+
+                      Token::Str("markup"),
+                      Token::None,
+
+                      Token::Str("glissando"),
+                      Token::Bool(false),
+
+                      Token::Str("grace"),
+                      Token::Bool(false),
+
+                      Token::Str("annotation"),
+                      Token::None,
+
+                      Token::Str("articulations"),
+                      Token::Seq { len: Some(0) },
+                      Token::SeqEnd,
+
+                      Token::Str("dynamic"),
+                      Token::None,
+
                       Token::StructEnd,
         ]);
     }
@@ -0,0 +1,263 @@
+//! The `partitura` module wraps rendered music in the rest of a Lilypond document: the `\score`
+//! block itself, the staves the music is placed on, and document-level settings such as the
+//! tempo.
+
+use serde::{Serialize, Serializer};
+use serde::ser::SerializeStruct;
+
+use super::{Duration, Durational};
+use super::notes::NoteLanguage;
+
+/// A metronome marking, given as a beat unit and the number of those beats per minute.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tempo<D>
+where D: Durational
+{
+    pub unit: Duration<D>,
+    pub bpm: u32
+}
+
+/// The clef at the start of a `Staff`.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum Clef {
+    #[default]
+    Treble,
+    Bass,
+    Alto,
+    Tenor,
+    /// Puts the staff in `\drummode`, for music written with `DrumPitch`es.
+    Percussion,
+    /// A single-line `RhythmicStaff`, for music written with `Unpitched` notes.
+    Rhythmic
+}
+
+/// A single staff holding some already-rendered music.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Staff {
+    pub music: String,
+    pub clef: Clef
+}
+
+/// Several `Staff`s printed together as one system, such as the two staves of a piano part.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StaffGroup {
+    staves: Vec<Staff>,
+    context: &'static str
+}
+
+/// A complete Lilypond document surrounding some already-rendered music.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Score<D>
+where D: Durational
+{
+    music: String,
+    tempo: Option<Tempo<D>>,
+    language: Option<NoteLanguage>
+}
+
+impl<D> Tempo<D>
+where D: Durational
+{
+    pub fn new<T: Into<Duration<D>>>(unit: T, bpm: u32) -> Self {
+        Tempo {
+            unit: unit.into(),
+            bpm
+        }
+    }
+
+    pub fn as_lilypond(&self) -> String {
+        format!("\\tempo {} = {}", self.unit.as_lilypond(), self.bpm)
+    }
+}
+
+impl Clef {
+    pub fn as_lilypond(&self) -> &'static str {
+        match *self {
+            Clef::Treble => "\\clef treble",
+            Clef::Bass => "\\clef bass",
+            Clef::Alto => "\\clef alto",
+            Clef::Tenor => "\\clef tenor",
+            Clef::Percussion | Clef::Rhythmic => "\\clef percussion"
+        }
+    }
+}
+
+impl Staff {
+    pub fn new(music: String, clef: Clef) -> Self {
+        Staff {
+            music,
+            clef
+        }
+    }
+
+    /// Writes the staff, which is a `\new DrumStaff` in `\drummode` for the percussion clef, and
+    /// a `\new RhythmicStaff` for the rhythmic one.
+    pub fn as_lilypond(&self) -> String {
+        match self.clef {
+            Clef::Percussion => format!("\\new DrumStaff \\drummode {{ {} }}", self.music.trim()),
+            Clef::Rhythmic => format!("\\new RhythmicStaff {{ {} }}", self.music.trim()),
+            clef => format!("\\new Staff {{ {} {} }}", clef.as_lilypond(), self.music.trim())
+        }
+    }
+}
+
+impl StaffGroup {
+    /// Brackets the staves together (`\new StaffGroup`), as for a section of an ensemble.
+    pub fn new(staves: Vec<Staff>) -> Self {
+        StaffGroup {
+            staves,
+            context: "StaffGroup"
+        }
+    }
+
+    /// Braces an upper staff in the treble clef and a lower one in the bass clef into a piano
+    /// part (`\new PianoStaff`).
+    pub fn piano_staff(upper: String, lower: String) -> Self {
+        StaffGroup {
+            staves: vec![Staff::new(upper, Clef::Treble), Staff::new(lower, Clef::Bass)],
+            context: "PianoStaff"
+        }
+    }
+
+    /// Braces an upper staff in the treble clef and a lower one in the bass clef (`\new
+    /// GrandStaff`), as for a harp or any other instrument written on two staves.
+    pub fn grand_staff(upper: String, lower: String) -> Self {
+        StaffGroup {
+            staves: vec![Staff::new(upper, Clef::Treble), Staff::new(lower, Clef::Bass)],
+            context: "GrandStaff"
+        }
+    }
+
+    /// Writes the staves, one on each line, so that the result can be given to `Score::new`.
+    pub fn as_lilypond(&self) -> String {
+        let staves: Vec<String> = self.staves.iter()
+            .map(|staff| format!("  {}\n", staff.as_lilypond()))
+            .collect();
+        format!("\\new {} <<\n{}>>", self.context, staves.concat())
+    }
+}
+
+impl<D> Score<D>
+where D: Durational
+{
+    pub fn new(music: String) -> Self {
+        Score {
+            music,
+            tempo: None,
+            language: None
+        }
+    }
+
+    /// Sets the tempo marking at the start of the score.
+    pub fn with_tempo(mut self, tempo: Tempo<D>) -> Self {
+        self.tempo = Some(tempo);
+        self
+    }
+
+    /// Declares the language the note names in the music are written in, with `\language`.
+    pub fn with_language(mut self, language: NoteLanguage) -> Self {
+        self.language = Some(language);
+        self
+    }
+}
+
+impl<D> Serialize for Score<D>
+where D: Durational
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        let mut s = serializer.serialize_struct("Score", 3)?;
+        s.serialize_field("music", &self.music)?;
+        s.serialize_field("tempo", &self.tempo.map(|t| t.as_lilypond()))?;
+        s.serialize_field("language", &self.language.map(|l| format!("\\language \"{}\"", l.name())))?;
+        s.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::*;
+    use super::super::scrittore::*;
+    use super::super::notes::{ETPitch, DrumPitch, SingleNote, RhythmNote, Unpitched};
+    use super::super::sequenza::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_quarter_tempo() {
+        let tempo = Tempo::new(RatioDuration(1, 4), 120);
+        assert_eq!("\\tempo 4 = 120", &tempo.as_lilypond());
+    }
+
+    #[test]
+    fn test_dotted_quarter_tempo() {
+        let tempo = Tempo::new(RatioDuration(3, 8), 60);
+        assert_eq!("\\tempo 4. = 60", &tempo.as_lilypond());
+    }
+
+    #[test]
+    fn test_render_score_with_tempo() {
+        let score = Score::new("c4 d4 e4 f4".to_string())
+            .with_tempo(Tempo::new(RatioDuration(1, 4), 120));
+        let mut view = View::new(None, BTreeMap::new()).unwrap();
+        let out = score.render(&mut view).unwrap();
+        assert_eq!("\\score {\n  {\n    \\tempo 4 = 120\n    c4 d4 e4 f4\n  }\n}\n", &out);
+    }
+
+    #[test]
+    fn test_render_score_with_language() {
+        let music = [ETPitch::new(61), ETPitch::from_lilypond("des").unwrap()].iter()
+            .map(|pitch| pitch.in_language(NoteLanguage::Deutsch).pitch())
+            .collect::<Vec<String>>()
+            .join(" ");
+        let score: Score<RatioDuration> = Score::new(music).with_language(NoteLanguage::Deutsch);
+        let mut view = View::new(None, BTreeMap::new()).unwrap();
+        let out = score.render(&mut view).unwrap();
+        assert_eq!("\\language \"deutsch\"\n\\score {\n  {\n    cis des\n  }\n}\n", &out);
+    }
+
+    #[test]
+    fn test_grand_staff() {
+        let group = StaffGroup::piano_staff("c'4 d'4".to_string(), "c2".to_string());
+        assert_eq!("\\new PianoStaff <<\n  \\new Staff { \\clef treble c'4 d'4 }\n  \\new Staff { \\clef bass c2 }\n>>",
+                   &group.as_lilypond());
+
+        let group = StaffGroup::grand_staff("e4".to_string(), "c4".to_string());
+        let score: Score<RatioDuration> = Score::new(group.as_lilypond());
+        let out = score.render_default().unwrap();
+        assert_eq!("\\score {\n  {\n    \\new GrandStaff <<\n  \\new Staff { \\clef treble e4 }\n  \\new Staff { \\clef bass c4 }\n>>\n  }\n}\n", &out);
+    }
+
+    #[test]
+    fn test_drum_staff() {
+        let notes: Notes<SingleNote<DrumPitch, RatioDuration>> = Notes::new(vec![
+            SingleNote::new(DrumPitch::BassDrum, RatioDuration(1, 4)),
+            SingleNote::new(DrumPitch::Snare, RatioDuration(1, 4)),
+            SingleNote::new(DrumPitch::BassDrum, RatioDuration(1, 8)),
+            SingleNote::new(DrumPitch::BassDrum, RatioDuration(1, 8)),
+            SingleNote::new(DrumPitch::Snare, RatioDuration(1, 4))
+        ]);
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![Box::new(Measure::from_time_signature(4, 4))];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        let music = format_notes(&notes, &mut controller).unwrap();
+        let staff = Staff::new(music, Clef::Percussion);
+        let score: Score<RatioDuration> = Score::new(staff.as_lilypond());
+        let out = score.render_default().unwrap();
+        assert_eq!("\\score {\n  {\n    \\new DrumStaff \\drummode { %m. 1\n bd4 sn4 bd8 bd8 sn4 | }\n  }\n}\n", &out);
+    }
+
+    #[test]
+    fn test_rhythmic_staff() {
+        let notes: Notes<RhythmNote<RatioDuration>> = Notes::new(
+            [(1, 4), (1, 8), (1, 8), (1, 2)].iter()
+                .map(|&duration| SingleNote::new(Unpitched, duration))
+                .collect());
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![Box::new(Measure::from_time_signature(4, 4))];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap()
+            .with_style(OutputStyle::Compact);
+        let music = format_notes(&notes, &mut controller).unwrap();
+        let staff = Staff::new(music, Clef::Rhythmic);
+        assert_eq!(staff.as_lilypond(), "\\new RhythmicStaff { c4 c8 c8 c4 ~ c4 | }");
+    }
+}
@@ -4,22 +4,79 @@
 use handlebars::{Handlebars, Helper, RenderContext, RenderError};
 use serde_json::{self, Value};
 use serde::{Serialize, Deserialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::marker::PhantomData;
 use std::error::Error;
+use std::cmp;
+use std::ops::{Add, Range};
+use std::fmt;
+use std::fs;
 use std::path::Path;
 
-use super::{Pitch, Durational, Note};
-use super::notes::{SingleNote, Chord};
+use super::{gcd, Pitch, Duration, Durational, RatioDuration, Note, ParseError};
+use super::notes::{ETPitch, SingleNote, Chord, Rest, NoteEvent, TiePolicy, Markup};
+use super::partitura::Clef;
+use super::sequenza::{Grouping, GroupingNode, GroupingController, OutputStyle, measure_comment};
+use super::partitura::Score;
 
 /// Homogeneous collection of Notes, implementing Viewable.
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Notes<N>
-where N: Note
+{
+    data: Vec<N>,
+    #[serde(default)]
+    spans: Vec<Span>
+}
+
+/// Version of the `SerializedScore` format written by this version of the crate.
+pub const SCORE_VERSION: u32 = 1;
+
+/// `Notes` wrapped with the version of the format they were saved in, for storing a score as JSON
+/// and reading it back with a later version of the crate.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SerializedScore<N>
+{
+    pub version: u32,
+    pub notes: Notes<N>
+}
+
+/// A marking that stretches across several notes of a passage, rather than belonging to any one.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SpanKind {
+    /// A crescendo hairpin (`\<`).
+    Crescendo,
+    /// A decrescendo hairpin (`\>`).
+    Decrescendo,
+    /// A text spanner (`\startTextSpan`), with a marking such as "poco a poco cresc." followed
+    /// by a dashed line up to the last note.
+    TextSpan(Markup)
+}
+
+/// A `SpanKind` running over a range of the notes of a passage, by index. The start of the span
+/// is written on the first note of the range, and its end on the last.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Span {
+    pub kind: SpanKind,
+    pub notes: Range<usize>
+}
+
+/// Builds up a passage of `Notes` one event at a time, from plain MIDI note numbers and duration
+/// ratios. Each method is available as long as the type of `Note` being collected can be created
+/// from that kind of event, so a builder of `SingleNote`s has no use for `chord`.
+pub struct NotesBuilder<N = SingleNote<ETPitch, RatioDuration>>
 {
     data: Vec<N>
 }
 
+/// Several streams of Notes sharing a single staff. Each voice is formatted against its own
+/// `GroupingController`, built fresh from `groupings`, so that barlines line up across voices.
+pub struct Voices<N, D>
+where D: Durational
+{
+    voices: Vec<Notes<N>>,
+    groupings: Box<dyn Fn() -> Vec<Box<dyn Grouping<D>>>>
+}
+
 pub struct SingleNoteView<P, D>
 {
     pub context: BTreeMap<String, Value>,
@@ -34,6 +91,33 @@ pub struct ChordView<P, D>
     phantom: PhantomData<(P, D)>
 }
 
+pub struct RestView<D>
+{
+    pub context: BTreeMap<String, Value>,
+    hb: Handlebars,
+    phantom: PhantomData<D>
+}
+
+/// Renders a `NoteEvent` with the template of whichever kind of note it holds. The note is placed
+/// in the context under the same name its own `View` would use (`note`, `chord` or `rest`), and the
+/// template branches on which one is present.
+pub struct NoteEventView<P, D>
+{
+    pub context: BTreeMap<String, Value>,
+    hb: Handlebars,
+    phantom: PhantomData<(P, D)>
+}
+
+/// Renders each of the `Notes` with its own default template. If the context has a `separator`
+/// string, the notes are joined with exactly that (a newline puts one note on each line);
+/// otherwise each note is padded with a space on either side.
+///
+/// Each note is rendered with the rest of this view's context, so per-note templates can read
+/// shared values such as a key or a dynamic. A partial registered as `note` (see
+/// `View::register_partial`) replaces the default template of every note.
+///
+/// An empty passage renders to an empty string, unless the template writes something more than
+/// whitespace around the notes.
 pub struct NotesView<N, D>
 {
     pub context: BTreeMap<String, Value>,
@@ -41,6 +125,57 @@ pub struct NotesView<N, D>
     phantom: PhantomData<(N, D)>
 }
 
+pub struct VoicesView<N, D>
+{
+    pub context: BTreeMap<String, Value>,
+    hb: Handlebars,
+    phantom: PhantomData<(N, D)>
+}
+
+pub struct ScoreView<D>
+{
+    pub context: BTreeMap<String, Value>,
+    hb: Handlebars,
+    phantom: PhantomData<D>
+}
+
+/// Renders `Notes` as a tune in ABC notation rather than Lilypond. The header fields are read from
+/// the context (`index`, `title`, `meter`, `unit_length` and `key`), and any that are missing are
+/// filled in with defaults.
+pub struct AbcView<D>
+{
+    pub context: BTreeMap<String, Value>,
+    hb: Handlebars,
+    phantom: PhantomData<D>
+}
+
+/// Error produced when a `View` fails to render, carrying a description of what went wrong, such
+/// as the note that a template could not read.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ViewError {
+    pub desc: String
+}
+
+impl From<&'static str> for ViewError {
+    fn from(desc: &'static str) -> Self {
+        ViewError { desc: desc.to_string() }
+    }
+}
+
+impl From<RenderError> for ViewError {
+    fn from(error: RenderError) -> Self {
+        ViewError { desc: error.desc }
+    }
+}
+
+impl fmt::Display for ViewError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.desc)
+    }
+}
+
+impl Error for ViewError {}
+
 /// The fundamental trait for scrittore module. By convention, `format()` instantiates a global
 /// variable as the expected name of the input. That is, a `SingleNoteView` will instantiate its Input
 /// data as the JSON object `note`.
@@ -48,26 +183,65 @@ pub trait View: Sized
 {
     type Input;
 
-    fn new(source: Option<String>, context: BTreeMap<String, Value>) -> Result<Self, Box<Error>>;
+    fn new(source: Option<String>, context: BTreeMap<String, Value>) -> Result<Self, Box<dyn Error>>;
 
-    fn new_boxed_view(source: Option<String>, context: BTreeMap<String, Value>) -> Result<Box<Self>, Box<Error>> 
+    fn new_boxed_view(source: Option<String>, context: BTreeMap<String, Value>) -> Result<Box<Self>, Box<dyn Error>> 
     {
         Self::new(source, context).map(|s| Box::new(s))
     }
 
     fn hb(&self) -> &Handlebars;
+
+    /// Gives access to the Handlebars registry, for registering partials or helpers, or replacing
+    /// the escape function. Changes take effect from the next call to `render`.
+    fn hb_mut(&mut self) -> &mut Handlebars;
+
+    /// Replaces the function that escapes the values written by `{{ }}` expressions. Views escape
+    /// nothing by default, which is right for Lilypond; `handlebars::html_escape` suits formats
+    /// such as HTML or XML, where characters like `<` must be escaped.
+    fn with_escape<F>(mut self, escape: F) -> Self 
+        where F: 'static + Fn(&str) -> String + Send + Sync
+    {
+        self.hb_mut().register_escape_fn(escape);
+        self
+    }
+
+    /// Registers a reusable snippet of template, which the `View`'s template (or another partial)
+    /// can include as `{{> name}}`. The partial sees the same context as the template including it.
+    fn register_partial(&mut self, name: &str, source: &str) -> Result<(), Box<dyn Error>> 
+    {
+        self.hb_mut().register_template_string(name, source)?;
+        Ok(())
+    }
     fn context(&self) -> &BTreeMap<String, Value>;
 
     fn load_context(&mut self, _: &Self::Input) -> Result<(), &'static str> { Ok(()) }
 
-    fn render<'b>(&'b mut self, input: &Self::Input) -> Result<String, &'static str> 
+    fn render(&mut self, input: &Self::Input) -> Result<String, ViewError> 
     {
         self.load_context(input)?;
-        self.hb().render("template", &self.context()).map_err(|_| "Could not render")
+        Ok(self.hb().render("template", &self.context())?)
+    }
+
+    /// Creates the `View` from a set of templates, keyed by `template_name()`. Any template
+    /// missing from the set falls back to the default.
+    fn from_templates(templates: &HashMap<&str, &str>, context: BTreeMap<String, Value>) -> Result<Self, Box<dyn Error>> 
+    {
+        let source = templates.get(Self::template_name()).map(|s| s.to_string());
+        Self::new(source, context)
+    }
+
+    /// Creates the `View` from a template file on disk, in place of the embedded default.
+    fn from_template_file<T: AsRef<Path>>(path: T, context: BTreeMap<String, Value>) -> Result<Self, Box<dyn Error>> 
+    {
+        let source = fs::read_to_string(path)?;
+        Self::new(Some(source), context)
     }
 
-    fn default_template_path() -> &'static Path { Path::new("") }
-    fn init_handlebars(source: Option<String>) -> Result<Handlebars, Box<Error>> 
+    /// Name of the `View`'s template in `default_templates()`.
+    fn template_name() -> &'static str { "" }
+
+    fn init_handlebars(source: Option<String>) -> Result<Handlebars, Box<dyn Error>> 
     {
         let mut hb = Handlebars::new();
         // Override the default with a no-escape function
@@ -75,12 +249,36 @@ pub trait View: Sized
         hb.register_escape_fn(escape_fn);
         match source {
             Some(s) => hb.register_template_string("template", s)?,
-            None => hb.register_template_file("template", Self::default_template_path())?
+            None => {
+                let source = default_templates().get(Self::template_name()).cloned()
+                    .ok_or("No default template for this View")?;
+                hb.register_template_string("template", source)?
+            }
         }
         Ok(hb)
     }
 }
 
+/// An object-safe counterpart to `View`, taking its input as JSON so that views of different
+/// `Input` types can be kept together, as in a `Vec<Box<dyn ErasedView>>`. Every `View` whose
+/// `Input` can be deserialized is an `ErasedView`.
+pub trait ErasedView
+{
+    /// Reads the input from `value`, then renders it as `View::render` would.
+    fn render_value(&mut self, value: &Value) -> Result<String, ViewError>;
+}
+
+impl<V> ErasedView for V 
+where V: View,
+      for<'de> V::Input: Deserialize<'de>
+{
+    fn render_value(&mut self, value: &Value) -> Result<String, ViewError> {
+        let input: V::Input = serde_json::from_value(value.clone())
+            .map_err(|_| "Could not read input from value")?;
+        self.render(&input)
+    }
+}
+
 /// `Viewable` sets up a given context allowing for a single element to be rendered. An object will
 /// receive a given `View`, and by convention insert itself into the data structure of that `View`
 /// before rendering.
@@ -89,12 +287,12 @@ where D: 'a + Durational
 {
     type View: View<Input=Self>;
 
-    fn render<'b>(&self, view: &'b mut Self::View) -> Result<String, &'static str> 
+    fn render(&self, view: &mut Self::View) -> Result<String, ViewError> 
     {
         view.render(self)
     }
 
-    fn render_default<'b>(&self) -> Result<String, &'static str> 
+    fn render_default(&self) -> Result<String, ViewError> 
     {
         Self::View::new(None, BTreeMap::new())
             .map_err(|_| "Could not create default View")?.render(self)
@@ -102,201 +300,2448 @@ where D: 'a + Durational
 }
 
 impl<N> Notes<N> 
-where N: Note
 {
     pub fn new(notes: Vec<N>) -> Self {
         Notes {
             data: notes,
+            spans: Vec::new()
         }
     }
-}
 
-impl<'a, P, D> View for SingleNoteView<P, D>
-where D: 'a + Durational + Serialize,
-      P: Pitch + Clone + Serialize,
-      for<'de> D: Deserialize<'de>
-{
-    type Input = SingleNote<P, D>;
+    /// Marks a range of the notes with a `SpanKind`, such as a hairpin. The range must hold at
+    /// least two notes, and hairpins may not overlap one another, although one may begin on the
+    /// note after another ends.
+    pub fn add_span(&mut self, kind: SpanKind, notes: Range<usize>) -> Result<(), &'static str> {
+        if notes.end > self.data.len() {
+            return Err("Span runs past the end of the passage");
+        }
+        if notes.end < notes.start + 2 {
+            return Err("Span must cover at least two notes");
+        }
+        let overlaps = self.spans.iter()
+            .any(|span| span.kind.is_hairpin() && span.notes.start < notes.end && notes.start < span.notes.end);
+        if kind.is_hairpin() && overlaps {
+            return Err("Hairpins cannot overlap");
+        }
+        self.spans.push(Span { kind, notes });
+        Ok(())
+    }
+
+    /// The spans marked over the passage, in the order they were added.
+    pub fn spans(&self) -> &[Span] {
+        &self.spans
+    }
+
+    /// Text written after the note at `index` for the spans beginning or ending there. Spans
+    /// ending on the note are written before those beginning on it.
+    fn span_marks(&self, index: usize) -> String {
+        let ends = self.spans.iter()
+            .filter(|span| span.notes.end.checked_sub(1) == Some(index))
+            .map(|span| span.kind.end().to_string());
+        let starts = self.spans.iter()
+            .filter(|span| span.notes.start == index)
+            .map(|span| span.kind.start());
+        ends.chain(starts).collect()
+    }
+
+    /// The notes of the passage, in order.
+    pub fn as_slice(&self) -> &[N] {
+        &self.data
+    }
+
+    /// Number of notes in the passage. A chord counts as a single note, and so does a rest.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Moves every note of `other` onto the end of this passage, leaving `other` empty.
+    pub fn append(&mut self, other: &mut Notes<N>) {
+        let offset = self.data.len();
+        self.spans.extend(other.spans.drain(..).map(|span| span.shifted(offset)));
+        self.data.append(&mut other.data);
+    }
+
+    /// Splices the notes of `other` into the passage, starting at `index`. Spans that run across
+    /// `index` are stretched over the new notes. Panics if `index` is past the end of the passage.
+    pub fn insert_at(&mut self, index: usize, other: Notes<N>) {
+        let count = other.data.len();
+        for span in self.spans.iter_mut() {
+            if span.notes.start >= index {
+                span.notes.start += count;
+            }
+            if span.notes.end > index {
+                span.notes.end += count;
+            }
+        }
+        self.spans.extend(other.spans.into_iter().map(|span| span.shifted(index)));
+        let tail = self.data.split_off(index);
+        self.data.extend(other.data);
+        self.data.extend(tail);
+    }
+
+    /// Splits the passage in two, with the note at `index` starting the second part. Spans that
+    /// run across `index` are cut in two, and any part left with fewer than two notes is dropped.
+    /// Panics if `index` is past the end of the passage.
+    pub fn split_at(mut self, index: usize) -> (Notes<N>, Notes<N>) {
+        let tail = self.data.split_off(index);
+        let mut tail = Notes::new(tail);
+        let spans: Vec<Span> = self.spans.drain(..).collect();
+        for span in spans {
+            let head_part = span.notes.start..cmp::min(span.notes.end, index);
+            let tail_part = cmp::max(span.notes.start, index) - index..span.notes.end.saturating_sub(index);
+            if head_part.end >= head_part.start + 2 {
+                self.spans.push(Span { kind: span.kind.clone(), notes: head_part });
+            }
+            if tail_part.end >= tail_part.start + 2 {
+                tail.spans.push(Span { kind: span.kind, notes: tail_part });
+            }
+        }
+        (self, tail)
+    }
 
-    fn new(source: Option<String>, context: BTreeMap<String, Value>) -> Result<Self, Box<Error>> 
+    /// Sum of the durations of every note in the passage, in lowest terms.
+    pub fn total_duration<D>(&self) -> Duration<D> 
+    where N: Note<D>,
+          D: Durational
     {
-        let hb: Handlebars = Self::init_handlebars(source)?;
-        let phantom = PhantomData;
-        Ok(SingleNoteView { context, hb, phantom })
+        self.data.iter().fold(Duration::new(0, 1), |acc, note| acc + note.duration())
     }
 
-    fn hb(&self) -> &Handlebars { &self.hb }
-    fn context(&self) -> &BTreeMap<String, Value> { &self.context }
+    /// The onset and offset of each note, measured from the start of the passage. Each note
+    /// occupies the half-open range from its onset up to, but not including, its offset, which
+    /// is also the onset of the note that follows.
+    pub fn onsets<D>(&self) -> Vec<(Duration<D>, Duration<D>)> 
+    where N: Note<D>,
+          D: Durational
+    {
+        let mut onset = Duration::new(0, 1);
+        self.data.iter().map(|note| {
+            let offset = onset + note.duration();
+            let range = (onset, offset);
+            onset = offset;
+            range
+        }).collect()
+    }
 
-    fn load_context(&mut self, input: &Self::Input) -> Result<(), &'static str> 
+    /// Snaps every onset in the passage to the nearest multiple of `grid` and rewrites each
+    /// duration to reach the following (snapped) onset, so the passage stays gapless and
+    /// grid-aligned. Onsets are the running sum of the durations, starting from zero, and the
+    /// end of the final note is snapped like any other onset.
+    ///
+    /// Since onsets come from a running sum, quantizing can never produce overlaps. A note whose
+    /// onset would snap onto the onset of the previous note keeps a single grid unit instead of
+    /// vanishing, pushing every later onset back by that unit. Grace notes are left alone.
+    pub fn quantize_to_grid<D>(&mut self, grid: Duration<D>)
+    where N: Note<D>,
+          D: Durational
     {
-        let in_val = serde_json::to_value(input).map_err(|_| "Could not parse note into value")?;
-        self.context.insert("note".to_string(), in_val);
-        Ok(())
+        let (grid_num, grid_den) = grid.as_ratio();
+        let mut onset = Duration::<D>::new(0, 1);
+        let mut start = 0;
+        for note in self.data.iter_mut().filter(|note| !note.is_grace()) {
+            onset = onset + note.duration();
+            let (num, den) = onset.as_ratio();
+            // Round (onset / grid) to the nearest whole number of grid units
+            let units = (2 * num as u64 * grid_den as u64 + den as u64 * grid_num as u64)
+                / (2 * den as u64 * grid_num as u64);
+            let end = cmp::max(units as u32, start + 1);
+            let num = (end - start) * grid_num;
+            let least = gcd(num, grid_den);
+            note.set_duration(Duration::new(num / least, grid_den / least));
+            start = end;
+        }
+    }
+}
+
+impl SpanKind {
+    fn is_hairpin(&self) -> bool {
+        match *self {
+            SpanKind::Crescendo | SpanKind::Decrescendo => true,
+            SpanKind::TextSpan(_) => false
+        }
+    }
+
+    /// Lilypond command written after the first note of the span. The text of a `TextSpan` is
+    /// attached to the command with a `\\tweak`, so nothing needs to be written before the note.
+    pub fn start(&self) -> String {
+        match *self {
+            SpanKind::Crescendo => "\\<".to_string(),
+            SpanKind::Decrescendo => "\\>".to_string(),
+            SpanKind::TextSpan(ref markup) => format!("{}\\tweak bound-details.left.text {} \\startTextSpan",
+                                                      markup.placement().as_lilypond(), markup.as_markup())
+        }
     }
 
-    fn default_template_path() -> &'static Path {
-        Path::new("templates/single_note.hbs")
+    /// Lilypond command written after the last note of the span.
+    pub fn end(&self) -> &'static str {
+        match *self {
+            SpanKind::Crescendo | SpanKind::Decrescendo => "\\!",
+            SpanKind::TextSpan(_) => "\\stopTextSpan"
+        }
     }
 }
 
-impl<'a, P, D> View for ChordView<P, D>
-where D: 'a + Durational + Serialize,
-      P: Pitch + Clone + Serialize,
-      for<'de> D: Deserialize<'de>
+impl Span {
+    fn shifted(self, offset: usize) -> Span {
+        Span {
+            kind: self.kind,
+            notes: self.notes.start + offset..self.notes.end + offset
+        }
+    }
+}
+
+impl<N> SerializedScore<N> 
 {
-    type Input = Chord<P, D>;
+    /// Wraps `notes` in the current `SCORE_VERSION`.
+    pub fn new(notes: Notes<N>) -> Self {
+        SerializedScore {
+            version: SCORE_VERSION,
+            notes
+        }
+    }
 
-    fn new(source: Option<String>, context: BTreeMap<String, Value>) -> Result<Self, Box<Error>> 
+    pub fn to_json(&self) -> Result<String, &'static str> 
+    where N: Serialize
     {
-        let hb: Handlebars = Self::init_handlebars(source)?;
-        let phantom = PhantomData;
-        Ok(ChordView { context, hb, phantom })
+        serde_json::to_string(self).map_err(|_| "Could not serialize score")
     }
 
-    fn hb(&self) -> &Handlebars { &self.hb }
-    fn context(&self) -> &BTreeMap<String, Value> { &self.context }
+    /// Reads a score saved with `to_json`. The version is checked before anything else is read,
+    /// so a score from a newer version of the crate is rejected even if the rest of it has
+    /// changed beyond recognition.
+    pub fn from_json(json: &str) -> Result<Self, &'static str> 
+    where for<'de> N: Deserialize<'de>
+    {
+        let value: Value = serde_json::from_str(json).map_err(|_| "Could not parse score")?;
+        let version = value.get("version").and_then(Value::as_u64)
+            .ok_or("Score has no version")?;
+        if version == 0 || version > SCORE_VERSION as u64 {
+            return Err("Score was saved with an unknown version");
+        }
+        serde_json::from_value(value).map_err(|_| "Could not read score")
+    }
+}
 
-    fn load_context(&mut self, input: &Self::Input) -> Result<(), &'static str> {
-        let in_val = serde_json::to_value(input).map_err(|_| "Could not parse chord into value")?;
-        self.context.insert("chord".to_string(), in_val);
-        Ok(())
+impl<N> Add for Notes<N> 
+{
+    type Output = Self;
+
+    /// Joins two passages, one after the other.
+    fn add(mut self, mut other: Self) -> Self {
+        self.append(&mut other);
+        self
     }
+}
 
-    fn default_template_path() -> &'static Path {
-        Path::new("templates/chord.hbs")
+impl<N> Extend<N> for Notes<N> 
+{
+    fn extend<T: IntoIterator<Item=N>>(&mut self, notes: T) {
+        self.data.extend(notes);
     }
 }
 
-impl<'a, D, N> View for NotesView<N, D>
-where D: 'a + Durational + Serialize,
-      N: Note + Clone + Serialize + Viewable<'a, D>,
-      for<'de> D: Deserialize<'de>,
-      for<'de> N: Deserialize<'de>
+impl<N> IntoIterator for Notes<N> 
 {
-    type Input = Notes<N>;
+    type Item = N;
+    type IntoIter = ::std::vec::IntoIter<N>;
 
-    fn new(source: Option<String>, context: BTreeMap<String, Value>) -> Result<Self, Box<Error>> {
-        let mut hb: Handlebars = Self::init_handlebars(source)?;
-        hb.register_template_file("note", "templates/single_note.hbs")?;
-        let view_note_helper = |h: &Helper, _: &Handlebars, rc: &mut RenderContext| -> Result<(), RenderError> {
-            let viewable_json = h.param(0).map(|v| v.value())
-                .ok_or(RenderError::new("Could not get param"))?;
-            let note: N = serde_json::from_value(viewable_json.clone())
-                .map_err(|e| RenderError::new(e.description()))?;
-            let out = note.render_default()
-                .map_err(|_| RenderError::new("Could not render"))?;
-            rc.writer.write(out.trim().as_bytes().as_ref())?;
-            Ok(())
-        };
-        hb.register_helper("view_note", Box::new(view_note_helper));
-        let phantom = PhantomData;
-        Ok(NotesView { context, hb, phantom })
+    fn into_iter(self) -> Self::IntoIter {
+        self.data.into_iter()
     }
+}
 
-    fn hb(&self) -> &Handlebars { &self.hb }
-    fn context(&self) -> &BTreeMap<String, Value> { &self.context }
+impl<D> Notes<SingleNote<ETPitch, D>> 
+where D: Durational
+{
+    /// The clef changes that keep a melody on the staff, starting from the treble clef. The
+    /// melody moves into the bass clef at the first note below `threshold` (a MIDI note number),
+    /// and back into the treble clef at the next note at or above it. Each change is given with
+    /// the index of the note it comes before.
+    pub fn clef_changes(&self, threshold: u32) -> Vec<(usize, Clef)> {
+        let mut current = Clef::Treble;
+        let mut changes = Vec::new();
+        for (index, note) in self.data.iter().enumerate() {
+            let clef = if note.pitch().midi < threshold { Clef::Bass } else { Clef::Treble };
+            if clef != current {
+                changes.push((index, clef));
+                current = clef;
+            }
+        }
+        changes
+    }
+}
 
-    fn load_context(&mut self, input: &Self::Input) -> Result<(), &'static str> {
-        let in_val = serde_json::to_value(&input.data).map_err(|_| "Could not parse notes into value")?;
-        self.context.insert("notes".to_string(), in_val);
-        Ok(())
+impl Notes<SingleNote<ETPitch, RatioDuration>> 
+{
+    /// Reads a simple melody line written in Lilypond, such as `c4 d8 e8 f2`. Each note is a
+    /// pitch (see `ETPitch::from_lilypond`) optionally followed by a duration; as in Lilypond, a
+    /// note without a duration takes the duration of the note before it, and the first note
+    /// defaults to a quarter.
+    pub fn from_lilypond(src: &str) -> Result<Self, ParseError> {
+        Notes::parse_lilypond(src, ETPitch::from_lilypond)
     }
 
-    fn default_template_path() -> &'static Path {
-        &Path::new("templates/notes.hbs")
+    /// A chromatic scale of `count` notes rising by semitones from `start`, each lasting
+    /// `duration`. The notes after the first take the default spelling for their pitch class, in
+    /// the language of `start`.
+    pub fn chromatic(start: ETPitch, count: usize, duration: (u32, u32)) -> Self {
+        let data = (0..count as u32)
+            .map(|offset| {
+                let pitch = if offset == 0 { start } else { ETPitch { midi: start.midi + offset, spelling: None, ..start } };
+                SingleNote::new(pitch, duration)
+            })
+            .collect();
+        Notes::new(data)
     }
-}
 
-macro_rules! viewable {
-    ($item:tt, $view:tt) => {
-        impl<'a, P, D> Viewable<'a, D> for $item<P, D>
-        where D: 'a + Durational + Serialize,
-            P: Pitch + Clone + Serialize,
-            for<'de> D: Deserialize<'de>
-        {
-            type View = $view<P, D>;
+    /// Reads a melody line written in Lilypond's `\relative` mode, where each note is placed in
+    /// the octave closest to the note before it (within a fourth, counting by letter names), and
+    /// `'` or `,` move it a further octave up or down. The first note is placed relative to
+    /// `start`.
+    pub fn from_lilypond_relative(start: ETPitch, src: &str) -> Result<Self, ParseError> {
+        let mut previous = start;
+        Notes::parse_lilypond(src, |text| {
+            let pitch = ETPitch::from_lilypond(text)?;
+            let spelling = pitch.spelling();
+            // Octave marks written on the note, as an offset from the unmarked octave
+            let marks = (pitch.midi as i32 - spelling.alteration - step(spelling.letter) - 60) / 12;
+            let letter = diatonic_index(spelling.letter);
+
+            let last = previous.spelling();
+            let last_octave = (previous.midi as i32 - last.alteration - step(last.letter)).div_euclid(12);
+            let distance = diatonic_index(last.letter) + 7 * last_octave - letter;
+            let octave = (distance + 3).div_euclid(7) + marks;
+
+            let midi = 12 * octave + step(spelling.letter) + spelling.alteration;
+            if midi < 0 {
+                return Err(ParseError::new(text, "Pitch is below MIDI note 0"));
+            }
+            let mut resolved = ETPitch::new(midi as u32);
+            resolved.spelling = Some(spelling);
+            previous = resolved;
+            Ok(resolved)
+        })
+    }
+
+    fn parse_lilypond<F>(src: &str, mut read_pitch: F) -> Result<Self, ParseError>
+        where F: FnMut(&str) -> Result<ETPitch, ParseError>
+    {
+        let mut duration = RatioDuration(1, 4);
+        let mut data = Vec::new();
+        for token in src.split_whitespace() {
+            let split = token.find(|c: char| c.is_ascii_digit()).unwrap_or(token.len());
+            let (pitch, ly_duration) = token.split_at(split);
+            let pitch = read_pitch(pitch)
+                .map_err(|e| ParseError::new(token, e.reason))?;
+            if !ly_duration.is_empty() {
+                duration = RatioDuration::from_lilypond(ly_duration)
+                    .map_err(|e| ParseError::new(token, e.reason))?;
+            }
+            data.push(SingleNote::new(pitch, duration));
         }
-    };
+        Ok(Notes::new(data))
+    }
 }
 
-viewable!(SingleNote, SingleNoteView);
-viewable!(Chord, ChordView);
-
-impl<'a, D, N> Viewable<'a, D> for Notes<N>
-where D: 'a + Durational + Serialize,
-      N: Note + Clone + Serialize + Viewable<'a, D>,
-      for<'de> D: Deserialize<'de>,
-      for<'de> N: Deserialize<'de>
+impl NotesBuilder 
 {
-    type View = NotesView<N, D>;
+    /// Creates a builder of `SingleNote`s. Builders of other kinds of `Note` are created with
+    /// `NotesBuilder::default()`.
+    pub fn new() -> Self {
+        Self::default()
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use super::super::*;
-    use super::super::notes::*;
-    
-    fn initialize_notes() -> Vec<SingleNote<ETPitch, RatioDuration>> {
-        vec![
-            SingleNote::new(ETPitch::new(60), Duration(RatioDuration(1, 2))),
-            SingleNote::new(ETPitch::new(62), Duration(RatioDuration(1, 4))),
-            SingleNote::new(ETPitch::new(64), Duration(RatioDuration(1, 4))),
-            SingleNote::new(ETPitch::new(65), Duration(RatioDuration(1, 4)))
-        ]
+impl<N> NotesBuilder<N> 
+{
+    /// Adds a single note with the given MIDI pitch and duration ratio.
+    pub fn note(mut self, midi: u32, duration: (u32, u32)) -> Self 
+        where N: From<SingleNote<ETPitch, RatioDuration>>
+    {
+        self.data.push(SingleNote::new(midi, duration).into());
+        self
     }
 
-    #[test]
-    fn test_render_note_custom_template() {
-        let notes = initialize_notes();
-        let context = BTreeMap::new();
-        let mut view = SingleNoteView::new(
-            Some("{{ note.text }}".to_string()),
-            context).unwrap();
+    /// Adds a series of single notes, each given as `(midi, (numerator, denominator))`.
+    pub fn notes(self, notes: &[(u32, (u32, u32))]) -> Self 
+        where N: From<SingleNote<ETPitch, RatioDuration>>
+    {
+        notes.iter().fold(self, |builder, &(midi, duration)| builder.note(midi, duration))
+    }
 
-        let out = notes[0].render(&mut view).unwrap();
-        assert_eq!("c", &out);
+    /// Adds a chord of the given MIDI pitches, all sharing one duration ratio.
+    pub fn chord(mut self, midi: &[u32], duration: (u32, u32)) -> Self 
+        where N: From<Chord<ETPitch, RatioDuration>>
+    {
+        let pitches: Vec<ETPitch> = midi.iter().map(|&m| ETPitch::new(m)).collect();
+        self.data.push(Chord::new(pitches, duration).into());
+        self
     }
 
-    #[test]
-    fn test_render_note_template() {
-        let notes = initialize_notes();
-        let context = BTreeMap::new();
-        let mut view = View::new(None, context).unwrap();
-        let out = notes[0].render(&mut view).unwrap();
-        assert_eq!("c2\n", out);
+    /// Adds a rest with the given duration ratio.
+    pub fn rest(mut self, duration: (u32, u32)) -> Self 
+        where N: From<Rest<RatioDuration>>
+    {
+        self.data.push(Rest::new(duration).into());
+        self
     }
 
-    #[test]
-    fn test_render_notes_template() {
-        let notes = Notes::new(initialize_notes());
-        let context = BTreeMap::new();
-        let mut view = View::new(None, context).unwrap();
-        let out = notes.render(&mut view).unwrap();
-        assert_eq!(" c2  d4  e4  f4 \n", out);
+    pub fn build(self) -> Notes<N> {
+        Notes::new(self.data)
     }
+}
 
-    #[test]
-    fn test_render_notes_with_chord_template() {
-        let notes = Notes::new(vec![
-                               Chord::new(vec![ETPitch::new(60), ETPitch::new(62)], 
-                                          RatioDuration(1, 2)),
-                               Chord::new(vec![ETPitch::new(64), ETPitch::new(65)], 
-                                          RatioDuration(1, 2))
-        ]);
-        let context = BTreeMap::new();
-        let mut view = View::new(None, context).unwrap();
+impl<N> Default for NotesBuilder<N> 
+{
+    fn default() -> Self {
+        NotesBuilder {
+            data: Vec::new()
+        }
+    }
+}
+
+impl<N, D> Voices<N, D>
+where D: Durational
+{
+    pub fn new<F>(voices: Vec<Notes<N>>, groupings: F) -> Self 
+        where F: 'static + Fn() -> Vec<Box<dyn Grouping<D>>>
+    {
+        Voices {
+            voices,
+            groupings: Box::new(groupings)
+        }
+    }
+
+    /// Formats each voice against its own `GroupingController`.
+    pub fn format(&self) -> Result<Vec<String>, &'static str> 
+        where N: Note<D>,
+              D: 'static
+    {
+        self.voices.iter().map(|voice| {
+            // An empty voice has nothing to place, so no groupings are needed for it
+            if voice.is_empty() {
+                return Ok(String::new());
+            }
+            let groupings = (self.groupings)();
+            let mut controller = GroupingController::new(Box::new(groupings.into_iter()))?;
+            format_notes(voice, &mut controller)
+        }).collect()
+    }
+}
+
+/// The default template for each `View`, compiled into the crate so that rendering does not depend
+/// on a `templates/` directory being present at runtime.
+pub fn default_templates() -> HashMap<&'static str, &'static str> {
+    let mut templates = HashMap::new();
+    templates.insert("single_note", include_str!("../templates/single_note.hbs"));
+    templates.insert("chord", include_str!("../templates/chord.hbs"));
+    templates.insert("rest", include_str!("../templates/rest.hbs"));
+    templates.insert("note_event", include_str!("../templates/note_event.hbs"));
+    templates.insert("notes", include_str!("../templates/notes.hbs"));
+    templates.insert("voices", include_str!("../templates/voices.hbs"));
+    templates.insert("score", include_str!("../templates/score.hbs"));
+    templates.insert("abc", include_str!("../templates/abc.hbs"));
+    templates
+}
+
+/// Formats a single `Note`, consuming its duration from the `GroupingController`. Wherever the
+/// note runs past the end of the current `Grouping` it is split and the pieces are tied together.
+/// A piece that can't be written as a single note value is split again into the longest plain or
+/// dotted value that fits, followed by the rest.
+/// The `start_annotation()` of each `Grouping` is written before the first note inside of it, and
+/// the `end_annotation()` after the last.
+///
+/// A note without any duration takes up no time in any `Grouping`, so it can't be placed and is
+/// reported as an error.
+pub fn format_note<N, D, G>(note: &N, controller: &mut GroupingController<D, G>) -> Result<String, &'static str> 
+where N: Note<D>,
+      D: Durational,
+      G: GroupingNode<D>
+{
+    let mut out = String::new();
+    format_note_into(note, controller, &mut out)?;
+    Ok(out)
+}
+
+/// Formats a single `Note` as in `format_note`, appending the text to `out`.
+pub fn format_note_into<N, D, G, W>(note: &N, controller: &mut GroupingController<D, G>, mut out: &mut W) -> Result<(), &'static str> 
+where N: Note<D>,
+      D: Durational,
+      G: GroupingNode<D>,
+      W: fmt::Write
+{
+    match controller.style() {
+        OutputStyle::Pretty => write_note(note, controller, &mut out, ""),
+        OutputStyle::Compact => write_note(note, controller, &mut CompactWriter::new(out), "")
+    }
+}
+
+/// One piece of a note as it is laid out against a `GroupingController`, along with the
+/// `Grouping`s that begin and end with it. See `NoteSteps`.
+struct NoteStep<G> {
+    /// Number of the measure the piece falls in.
+    measure: u32,
+    /// Start annotations of the `Grouping`s beginning with the piece, from the outermost in.
+    starts: Vec<String>,
+    /// The piece itself, with the note's prefix, annotations and marks on its first piece.
+    text: String,
+    /// Whether the piece is tied to the one after it, or to the next note.
+    tied: bool,
+    /// Joins the piece to what follows: the tie, or a space between the pieces of a rest.
+    tie: String,
+    /// The note's suffix, after its last piece.
+    suffix: String,
+    /// The `Grouping`s the piece ends, whose end annotations follow it.
+    ended: Vec<G>
+}
+
+/// Lays a note out against a `GroupingController` one piece at a time, consuming the time of
+/// each piece as it goes. Wherever the note runs past the end of the current `Grouping` it is
+/// split, and a piece that can't be written as a single note value is split again into the
+/// longest plain or dotted value that fits, followed by the rest. Both `write_note` and
+/// `measurize` are written in terms of these pieces.
+struct NoteSteps<'a, N: 'a, D: 'a, G: 'a> 
+where D: Durational
+{
+    note: &'a N,
+    controller: &'a mut GroupingController<D, G>,
+    marks: &'a str,
+    remaining: Duration<D>,
+    grace: bool
+}
+
+impl<'a, N, D, G> NoteSteps<'a, N, D, G> 
+where N: Note<D>,
+      D: Durational,
+      G: GroupingNode<D>
+{
+    /// Checks that the note can be laid out before any of it is. A note without any duration
+    /// takes up no time in any `Grouping`, so it can't be placed, and a note that may not be tied
+    /// can't be split.
+    fn new(note: &'a N, controller: &'a mut GroupingController<D, G>, marks: &'a str) -> Result<Self, &'static str> {
+        let remaining = note.duration();
+        let grace = note.is_grace();
+        if !grace {
+            if remaining.as_float() == 0.0 {
+                return Err("Cannot format a note with zero duration");
+            }
+            if note.tie() == TiePolicy::Never && remaining > controller.current()?.left {
+                return Err("Note with TiePolicy::Never would be split across a grouping");
+            }
+        }
+        Ok(NoteSteps { note, controller, marks, remaining, grace })
+    }
+
+    /// A grace note takes no time, so it is a single piece that neither starts nor ends any
+    /// `Grouping`. Giving it the start annotations would write them again before the note that
+    /// follows.
+    fn grace_step(&mut self) -> NoteStep<G> {
+        let note = self.note;
+        let mut text = note.prefix();
+        text.push_str(&note.text());
+        text.push_str(&note.notated_duration().as_lilypond_in_tuplet(self.controller.time_scale()));
+        text.push_str(&note.annotations());
+        text.push_str(self.marks);
+        NoteStep {
+            measure: self.controller.measure_number(),
+            starts: Vec::new(),
+            text,
+            tied: false,
+            tie: String::new(),
+            suffix: note.suffix(),
+            ended: Vec::new()
+        }
+    }
+
+    fn step(&mut self) -> Result<NoteStep<G>, &'static str> {
+        let note = self.note;
+        let controller = &mut *self.controller;
+        let compact = controller.style() == OutputStyle::Compact;
+        let measure = controller.measure_number();
+        let starts = controller.starting()
+            .map(|grouping| (grouping.is_measure(), grouping.numbered_start_annotation(measure)))
+            // Compact output leaves out the comment giving the number of a measure, which would
+            // comment out the rest of the line
+            .filter(|&(is_measure, ref start)| !(compact && is_measure && *start == measure_comment(measure)))
+            .map(|(_, start)| start)
+            .collect();
+
+        let scale = controller.time_scale();
+        let current = controller.current()?;
+        let left = current.left;
+        let step = printable_step(if self.remaining > left { left } else { self.remaining }, scale);
+        // A beam opens on the first of several notes in a beamed grouping, and closes on the
+        // note that ends it, before any tie into the next grouping
+        let beam = match (current.grouping.is_beamed(), current.is_start_of_grouping()) {
+            (true, true) if step < left => "[",
+            (true, false) if step == left => "]",
+            _ => ""
+        };
+        let is_onset = self.remaining == note.duration();
+        self.remaining = self.remaining - step;
+        let is_last = self.remaining.as_float() == 0.0;
+
+        let mut text = if is_onset { note.prefix() } else { String::new() };
+        text.push_str(&note.text());
+        // A note that fits without being split is printed with its notated value
+        if step == note.duration() {
+            text.push_str(&note.notated_duration().as_lilypond_in_tuplet(scale));
+        } else {
+            text.push_str(&step.as_lilypond_in_tuplet(scale));
+        }
+        text.push_str(beam);
+        if is_onset {
+            text.push_str(&note.annotations());
+            text.push_str(self.marks);
+        }
+        // The pieces of a rest follow one another without a tie
+        let tied = !note.is_rest() && (!is_last || note.tie() == TiePolicy::Force);
+        let tie = match (note.is_rest(), is_last) {
+            (true, false) => " ".to_string(),
+            (false, false) => controller.tie().to_string(),
+            (false, true) if tied => controller.tie().trim_end().to_string(),
+            _ => String::new()
+        };
+        let suffix = if is_last { note.suffix() } else { String::new() };
+        let ended = controller.consume_time(step)?;
+        Ok(NoteStep { measure, starts, text, tied, tie, suffix, ended })
+    }
+}
+
+impl<'a, N, D, G> Iterator for NoteSteps<'a, N, D, G> 
+where N: Note<D>,
+      D: Durational,
+      G: GroupingNode<D>
+{
+    type Item = Result<NoteStep<G>, &'static str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.grace {
+            self.grace = false;
+            self.remaining = Duration::new(0, 1);
+            return Some(Ok(self.grace_step()));
+        }
+        if self.remaining.as_float() <= 0.0 {
+            return None;
+        }
+        let step = self.step();
+        if step.is_err() {
+            self.remaining = Duration::new(0, 1);
+        }
+        Some(step)
+    }
+}
+
+/// Writes a note as described in `format_note`. The `marks` belong to the passage rather than the
+/// note itself (such as the ends of `Span`s), and are written after the note's own annotations.
+fn write_note<N, D, G, W>(note: &N, controller: &mut GroupingController<D, G>, out: &mut W, marks: &str) -> Result<(), &'static str> 
+where N: Note<D>,
+      D: Durational,
+      G: GroupingNode<D>,
+      W: LineWriter
+{
+    let bar_checks = controller.bar_checks();
+    let line_breaks = controller.line_breaks();
+    for step in NoteSteps::new(note, controller, marks)? {
+        let step = step?;
+        for start in step.starts.iter() {
+            write_text(out, start)?;
+        }
+        write_text(out, &step.text)?;
+        write_text(out, &step.tie)?;
+        write_text(out, &step.suffix)?;
+        for grouping in step.ended.iter() {
+            if bar_checks && grouping.is_measure() {
+                write_bar_check(out, grouping.end_annotation())?;
+            } else {
+                write_text(out, grouping.end_annotation())?;
+            }
+            if grouping.is_measure() && line_breaks.is_some_and(|every| step.measure.is_multiple_of(every)) {
+                out.end_line().map_err(|_| "Could not write to the output buffer")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes the end of a `Measure` followed by a bar check, unless the barline is already a bar
+/// check. The bar check goes before the line break, so the next measure still starts a new line.
+fn write_bar_check<W>(out: &mut W, end: &str) -> Result<(), &'static str> 
+where W: fmt::Write
+{
+    let barline = end.trim_end();
+    if barline.ends_with('|') {
+        return write_text(out, end);
+    }
+    write_text(out, barline)?;
+    write_text(out, " |")?;
+    write_text(out, &end[barline.len()..])
+}
+
+/// Formats each of the `Notes` in turn with `format_note`, separated by spaces.
+pub fn format_notes<N, D, G>(notes: &Notes<N>, controller: &mut GroupingController<D, G>) -> Result<String, &'static str> 
+where N: Note<D>,
+      D: Durational,
+      G: GroupingNode<D>
+{
+    // A rough guess of a few bytes per note saves most of the reallocation
+    let mut out = String::with_capacity(notes.data.len() * 8);
+    format_notes_into(notes, controller, &mut out)?;
+    Ok(out)
+}
+
+/// Formats each of the `Notes` as in `format_notes`, appending the text to `out`. Passing the
+/// same buffer for several passages avoids allocating and joining a `String` for each note.
+pub fn format_notes_into<N, D, G, W>(notes: &Notes<N>, controller: &mut GroupingController<D, G>, mut out: &mut W) -> Result<(), &'static str> 
+where N: Note<D>,
+      D: Durational,
+      G: GroupingNode<D>,
+      W: fmt::Write
+{
+    match controller.style() {
+        OutputStyle::Pretty => write_notes(notes, controller, &mut out, &[]),
+        OutputStyle::Compact => write_notes(notes, controller, &mut CompactWriter::new(out), &[])
+    }
+}
+
+/// Formats a melody as in `format_notes`, switching clefs wherever `Notes::clef_changes` places
+/// them, so that a melody with a wide range stays on the staff. The melody starts in the treble
+/// clef.
+pub fn format_notes_with_clefs<D, G>(notes: &Notes<SingleNote<ETPitch, D>>, threshold: u32, controller: &mut GroupingController<D, G>) -> Result<String, &'static str> 
+where D: Durational,
+      G: GroupingNode<D>
+{
+    let clefs = notes.clef_changes(threshold);
+    let mut out = String::with_capacity(notes.data.len() * 8);
+    match controller.style() {
+        OutputStyle::Pretty => write_notes(notes, controller, &mut &mut out, &clefs)?,
+        OutputStyle::Compact => write_notes(notes, controller, &mut CompactWriter::new(&mut out), &clefs)?
+    }
+    Ok(out)
+}
+
+/// Writes the notes as described in `format_notes`, with each of the `clefs` written before the
+/// note at its index.
+fn write_notes<N, D, G, W>(notes: &Notes<N>, controller: &mut GroupingController<D, G>, out: &mut W, clefs: &[(usize, Clef)]) -> Result<(), &'static str> 
+where N: Note<D>,
+      D: Durational,
+      G: GroupingNode<D>,
+      W: LineWriter
+{
+    let mut clefs = clefs.iter().peekable();
+    for (index, note) in notes.data.iter().enumerate() {
+        if index > 0 {
+            write_text(out, " ")?;
+        }
+        if let Some(&&(_, clef)) = clefs.peek().filter(|&&&(at, _)| at == index) {
+            write_text(out, clef.as_lilypond())?;
+            write_text(out, " ")?;
+            clefs.next();
+        }
+        write_note(note, controller, out, &notes.span_marks(index))?;
+    }
+    Ok(())
+}
+
+/// Passes text through to another writer with every run of whitespace collapsed to a single
+/// space. Whitespace at the start is dropped, and so is whitespace at the end, since a space is
+/// only written once something follows it.
+struct CompactWriter<'w, W: 'w> {
+    out: &'w mut W,
+    started: bool,
+    space: bool,
+    line: bool
+}
+
+impl<'w, W> CompactWriter<'w, W> 
+where W: fmt::Write
+{
+    fn new(out: &'w mut W) -> Self {
+        CompactWriter {
+            out,
+            started: false,
+            space: false,
+            line: false
+        }
+    }
+}
+
+impl<'w, W> fmt::Write for CompactWriter<'w, W> 
+where W: fmt::Write
+{
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        for c in text.chars() {
+            if c.is_whitespace() {
+                self.space = self.started;
+            } else {
+                if self.line {
+                    self.out.write_char('\n')?;
+                } else if self.space {
+                    self.out.write_char(' ')?;
+                }
+                self.space = false;
+                self.line = false;
+                self.out.write_char(c)?;
+                self.started = true;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'w, W> LineWriter for CompactWriter<'w, W> 
+where W: fmt::Write
+{
+    /// The whitespace before whatever is written next becomes a line break.
+    fn end_line(&mut self) -> fmt::Result {
+        self.line = self.started;
+        Ok(())
+    }
+}
+
+/// A writer that formatted notes can be broken into lines on, as set by
+/// `GroupingController::with_line_breaks`.
+trait LineWriter: fmt::Write {
+    fn end_line(&mut self) -> fmt::Result;
+}
+
+/// Text written in the pretty style already starts a new line at every measure, so it is left
+/// as it is.
+impl<W> LineWriter for &mut W 
+where W: fmt::Write + ?Sized
+{
+    fn end_line(&mut self) -> fmt::Result {
+        Ok(())
+    }
+}
+
+/// Turns onset times in seconds into a passage of middle Cs, one per gap between onsets, with
+/// each duration snapped to the nearest `1 / grid` of a whole note at `bpm` quarter notes per
+/// minute. The onsets themselves are snapped, rather than the gaps between them, so rounding
+/// errors do not build up over a long passage; as in `quantize_to_grid`, a note that would
+/// snap to nothing keeps a single grid unit.
+pub fn quantize(onsets: &[f64], bpm: f64, grid: u32) -> Result<Notes<SingleNote<ETPitch, RatioDuration>>, &'static str> {
+    if grid == 0 {
+        return Err("Grid must be at least one note per whole note");
+    }
+    if bpm.is_nan() || bpm <= 0. {
+        return Err("Tempo must be positive");
+    }
+    let first = match onsets.first() {
+        Some(first) => *first,
+        None => return Ok(Notes::new(Vec::new()))
+    };
+    // Seconds to whole notes, at four quarters per whole note
+    let whole_notes = |seconds: f64| (seconds - first) * bpm / 60. / 4.;
+    let mut start = 0;
+    let mut data = Vec::new();
+    for onset in onsets.iter().skip(1) {
+        let end = cmp::max((whole_notes(*onset) * grid as f64).round() as i64, start + 1);
+        let duration = Duration::from_float((end - start) as f64 / grid as f64, grid)?;
+        data.push(SingleNote::new(ETPitch::new(60), duration.0));
+        start = end;
+    }
+    Ok(Notes::new(data))
+}
+
+/// Builds a melody by walking from `start` by each of the `intervals` in turn, in semitones
+/// (negative intervals go down), and giving each resulting pitch the matching duration. The
+/// starting pitch is only the point the walk begins from; begin `intervals` with a 0 to make it
+/// the first note. The two slices must be the same length, and the walk must stay within MIDI
+/// note numbers.
+pub fn melody_from_intervals(start: ETPitch, intervals: &[i32], durations: &[(u32, u32)]) -> Result<Notes<SingleNote<ETPitch, RatioDuration>>, &'static str> {
+    if intervals.len() != durations.len() {
+        return Err("Melody needs exactly one duration for each interval");
+    }
+    let mut midi = start.midi as i32;
+    let mut data = Vec::with_capacity(intervals.len());
+    for (interval, duration) in intervals.iter().zip(durations.iter()) {
+        midi += interval;
+        if midi < 0 {
+            return Err("Melody goes below MIDI note 0");
+        }
+        let pitch = ETPitch::new(midi as u32).in_language(start.language);
+        data.push(SingleNote::new(pitch, RatioDuration(duration.0, duration.1)));
+    }
+    Ok(Notes::new(data))
+}
+
+/// Position of a letter name within the octave, counting white keys from C.
+fn diatonic_index(letter: char) -> i32 {
+    "cdefgab".find(letter).map(|index| index as i32).unwrap_or(0)
+}
+
+/// Semitones from C up to a letter name.
+fn step(letter: char) -> i32 {
+    [0, 2, 4, 5, 7, 9, 11][diatonic_index(letter) as usize]
+}
+
+/// A single measure of a passage, as laid out by `measurize`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderedMeasure {
+    /// Number of the measure, counting from 1, or 0 for notes that fall outside of any `Measure`.
+    pub index: u32,
+    pub notes: Vec<RenderedNote>
+}
+
+impl RenderedMeasure {
+    /// Writes the measure as `format_notes` writes it in the pretty style, along with the
+    /// annotations of the `Grouping`s that begin and end within it.
+    pub fn as_lilypond(&self) -> String {
+        self.notes.iter().map(RenderedNote::as_lilypond).collect()
+    }
+}
+
+/// Writes each of the `measurize`d measures in turn, giving the same text as `format_notes` in
+/// the given `style`, which should be the style of the controller the measures were laid out
+/// against. Line breaks set with `GroupingController::with_line_breaks` are not written.
+pub fn render_measures(measures: &[RenderedMeasure], style: OutputStyle) -> Result<String, &'static str> {
+    let rendered = measures.iter()
+        .map(|measure| render_measure(measure, style))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(join_measures(rendered))
+}
+
+/// Writes the measures as `render_measures` does, but on rayon's thread pool. Each measure is
+/// written on its own, since `measurize` has already placed every note, and the measures are put
+/// back together in order.
+#[cfg(feature = "rayon")]
+pub fn render_parallel(measures: &[RenderedMeasure], style: OutputStyle) -> Result<String, &'static str> {
+    use rayon::prelude::*;
+    let rendered = measures.par_iter()
+        .map(|measure| render_measure(measure, style))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(join_measures(rendered))
+}
+
+/// A measure written in `style`, along with whether its text begins and ends with whitespace.
+/// Compact output leaves that whitespace out of the measure, and `join_measures` puts a single
+/// space back in its place.
+fn render_measure(measure: &RenderedMeasure, style: OutputStyle) -> Result<(String, bool, bool), &'static str> {
+    let text = measure.as_lilypond();
+    match style {
+        OutputStyle::Pretty => Ok((text, false, false)),
+        OutputStyle::Compact => {
+            let mut compact = String::with_capacity(text.len());
+            write_text(&mut CompactWriter::new(&mut compact), &text)?;
+            Ok((compact, text.starts_with(char::is_whitespace), text.ends_with(char::is_whitespace)))
+        }
+    }
+}
+
+/// Puts the measures written by `render_measure` back together in order.
+fn join_measures(measures: Vec<(String, bool, bool)>) -> String {
+    let mut out = String::new();
+    let mut space = false;
+    for (text, starts, ends) in measures {
+        // A measure of nothing but whitespace only separates the measures around it
+        if text.is_empty() {
+            space = space || starts;
+            continue;
+        }
+        if !out.is_empty() && (space || starts) {
+            out.push(' ');
+        }
+        out.push_str(&text);
+        space = ends;
+    }
+    out
+}
+
+/// One piece of a note within a `RenderedMeasure`, such as `c4`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderedNote {
+    /// Written before the piece: the space after the note before it, and the start annotations
+    /// of the `Grouping`s the piece begins.
+    pub start: String,
+    pub text: String,
+    /// Whether this piece is tied to the next one.
+    pub tied: bool,
+    /// The tie written after a tied piece, as set by `GroupingController::with_tie`, or the space
+    /// between the pieces of a rest.
+    pub tie: String,
+    /// Written after the piece: the note's suffix after its last piece, and the end annotations
+    /// of the `Grouping`s the piece ends.
+    pub end: String
+}
+
+impl RenderedNote {
+    pub fn as_lilypond(&self) -> String {
+        [&self.start, &self.text, &self.tie, &self.end].iter().map(|part| part.as_str()).collect()
+    }
+}
+
+/// Splits a passage into measures, so that each one can be written on its own. Notes are split
+/// wherever they cross a `Grouping` boundary, as in `format_note`, and the annotations of the
+/// `Grouping`s are kept with the pieces they begin and end, so that `render_measures` writes the
+/// same text as `format_notes`.
+pub fn measurize<N, D, G>(notes: &Notes<N>, controller: &mut GroupingController<D, G>) -> Result<Vec<RenderedMeasure>, &'static str> 
+where N: Note<D>,
+      D: Durational,
+      G: GroupingNode<D>
+{
+    let bar_checks = controller.bar_checks();
+    let mut measures: Vec<RenderedMeasure> = Vec::new();
+    for (note_index, note) in notes.data.iter().enumerate() {
+        let marks = notes.span_marks(note_index);
+        // As in `write_notes`, the notes are separated by spaces
+        let mut separator = if note_index > 0 { " " } else { "" };
+        for step in NoteSteps::new(note, controller, &marks)? {
+            let step = step?;
+            let start = step.starts.iter().fold(separator.to_string(), |start, annotation| start + annotation);
+            separator = "";
+            let mut end = step.suffix;
+            for grouping in step.ended.iter() {
+                if bar_checks && grouping.is_measure() {
+                    write_bar_check(&mut end, grouping.end_annotation())?;
+                } else {
+                    end.push_str(grouping.end_annotation());
+                }
+            }
+
+            if measures.last().map(|measure| measure.index) != Some(step.measure) {
+                measures.push(RenderedMeasure { index: step.measure, notes: Vec::new() });
+            }
+            if let Some(measure) = measures.last_mut() {
+                measure.notes.push(RenderedNote { start, text: step.text, tied: step.tied, tie: step.tie, end });
+            }
+        }
+    }
+    Ok(measures)
+}
+
+/// The part of `step` written as a single note inside of a tuplet of the given `scale`. Where
+/// `step` can't be written as one note value, this is the longest value that can, leaving the
+/// rest to be tied on after it.
+fn printable_step<D>(step: Duration<D>, scale: (u32, u32)) -> Duration<D> 
+where D: Durational
+{
+    let (num, den) = step.as_ratio();
+    let written = Duration::<D>::new(num * scale.0, den * scale.1).reduced();
+    let printable = written.0.longest_printable().as_ratio();
+    if printable == written.as_ratio() {
+        return step;
+    }
+    let (num, den) = (printable.0 * scale.1, printable.1 * scale.0);
+    let least = gcd(num, den);
+    Duration::new(num / least, den / least)
+}
+
+fn write_text<W>(out: &mut W, text: &str) -> Result<(), &'static str> 
+where W: fmt::Write
+{
+    out.write_str(text).map_err(|_| "Could not write to the output buffer")
+}
+
+impl<P, D> View for SingleNoteView<P, D>
+where D: Durational + Serialize,
+      P: Pitch + Clone + Serialize,
+      for<'de> D: Deserialize<'de>
+{
+    type Input = SingleNote<P, D>;
+
+    fn new(source: Option<String>, context: BTreeMap<String, Value>) -> Result<Self, Box<dyn Error>> 
+    {
+        let hb: Handlebars = Self::init_handlebars(source)?;
+        let phantom = PhantomData;
+        Ok(SingleNoteView { context, hb, phantom })
+    }
+
+    fn hb(&self) -> &Handlebars { &self.hb }
+    fn hb_mut(&mut self) -> &mut Handlebars { &mut self.hb }
+    fn context(&self) -> &BTreeMap<String, Value> { &self.context }
+
+    fn load_context(&mut self, input: &Self::Input) -> Result<(), &'static str> 
+    {
+        let in_val = serde_json::to_value(input).map_err(|_| "Could not parse note into value")?;
+        self.context.insert("note".to_string(), in_val);
+        Ok(())
+    }
+
+    fn template_name() -> &'static str { "single_note" }
+}
+
+impl<P, D> View for ChordView<P, D>
+where D: Durational + Serialize,
+      P: Pitch + Clone + Serialize,
+      for<'de> D: Deserialize<'de>
+{
+    type Input = Chord<P, D>;
+
+    fn new(source: Option<String>, context: BTreeMap<String, Value>) -> Result<Self, Box<dyn Error>> 
+    {
+        let hb: Handlebars = Self::init_handlebars(source)?;
+        let phantom = PhantomData;
+        Ok(ChordView { context, hb, phantom })
+    }
+
+    fn hb(&self) -> &Handlebars { &self.hb }
+    fn hb_mut(&mut self) -> &mut Handlebars { &mut self.hb }
+    fn context(&self) -> &BTreeMap<String, Value> { &self.context }
+
+    fn load_context(&mut self, input: &Self::Input) -> Result<(), &'static str> {
+        let in_val = serde_json::to_value(input).map_err(|_| "Could not parse chord into value")?;
+        self.context.insert("chord".to_string(), in_val);
+        Ok(())
+    }
+
+    fn template_name() -> &'static str { "chord" }
+}
+
+impl<D> View for RestView<D>
+where D: Durational + Serialize
+{
+    type Input = Rest<D>;
+
+    fn new(source: Option<String>, context: BTreeMap<String, Value>) -> Result<Self, Box<dyn Error>> 
+    {
+        let hb: Handlebars = Self::init_handlebars(source)?;
+        let phantom = PhantomData;
+        Ok(RestView { context, hb, phantom })
+    }
+
+    fn hb(&self) -> &Handlebars { &self.hb }
+    fn hb_mut(&mut self) -> &mut Handlebars { &mut self.hb }
+    fn context(&self) -> &BTreeMap<String, Value> { &self.context }
+
+    fn load_context(&mut self, input: &Self::Input) -> Result<(), &'static str> {
+        let in_val = serde_json::to_value(input).map_err(|_| "Could not parse rest into value")?;
+        self.context.insert("rest".to_string(), in_val);
+        Ok(())
+    }
+
+    fn template_name() -> &'static str { "rest" }
+}
+
+impl<P, D> View for NoteEventView<P, D>
+where D: Durational + Serialize,
+      P: Pitch + Clone + Serialize,
+      for<'de> D: Deserialize<'de>
+{
+    type Input = NoteEvent<P, D>;
+
+    fn new(source: Option<String>, context: BTreeMap<String, Value>) -> Result<Self, Box<dyn Error>> 
+    {
+        let mut hb: Handlebars = Self::init_handlebars(source)?;
+        let templates = default_templates();
+        for name in ["single_note", "chord", "rest"].iter() {
+            hb.register_template_string(name, templates[name])?;
+        }
+        let phantom = PhantomData;
+        Ok(NoteEventView { context, hb, phantom })
+    }
+
+    fn hb(&self) -> &Handlebars { &self.hb }
+    fn hb_mut(&mut self) -> &mut Handlebars { &mut self.hb }
+    fn context(&self) -> &BTreeMap<String, Value> { &self.context }
+
+    fn load_context(&mut self, input: &Self::Input) -> Result<(), &'static str> 
+    {
+        let in_val = serde_json::to_value(input).map_err(|_| "Could not parse note into value")?;
+        let name = match *input {
+            NoteEvent::SingleNote(_) => "note",
+            NoteEvent::Chord(_) => "chord",
+            NoteEvent::Rest(_) => "rest"
+        };
+        for key in ["note", "chord", "rest"].iter() {
+            self.context.remove(*key);
+        }
+        self.context.insert(name.to_string(), in_val);
+        Ok(())
+    }
+
+    fn template_name() -> &'static str { "note_event" }
+}
+
+impl<'a, D, N> View for NotesView<N, D>
+where D: 'a + Durational + Serialize,
+      N: Note<D> + Clone + Serialize + Viewable<'a, D>,
+      for<'de> D: Deserialize<'de>,
+      for<'de> N: Deserialize<'de>
+{
+    type Input = Notes<N>;
+
+    fn new(source: Option<String>, context: BTreeMap<String, Value>) -> Result<Self, Box<dyn Error>> {
+        let mut hb: Handlebars = Self::init_handlebars(source)?;
+        let view_note_helper = |h: &Helper, hb: &Handlebars, rc: &mut RenderContext| -> Result<(), RenderError> {
+            let viewable_json = h.param(0).map(|v| v.value())
+                .ok_or(RenderError::new("Could not get param"))?;
+            // Inside of `#each`, the position of the note points to the one that couldn't be read
+            let position = rc.get_local_var(&"@index".to_string())
+                .and_then(|index| index.as_u64())
+                .map(|index| format!(" at index {}", index))
+                .unwrap_or_default();
+            let note: N = serde_json::from_value(viewable_json.clone())
+                .map_err(|e| RenderError::new(format!("Could not read note{}: {}", position, e)))?;
+            // Everything but the passage itself is shared with the note's own view
+            let context: BTreeMap<String, Value> = match *rc.context().data() {
+                Value::Object(ref map) => map.iter()
+                    .filter(|&(key, _)| key != "notes")
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect(),
+                _ => BTreeMap::new()
+            };
+            let mut view = N::View::new(None, context)
+                .map_err(|e| RenderError::new(e.to_string()))?;
+            let out = if hb.get_template("note").is_some() {
+                view.load_context(&note).map_err(RenderError::new)?;
+                hb.render("note", view.context())?
+            } else {
+                note.render(&mut view).map_err(|e| RenderError::new(e.desc))?
+            };
+            rc.writer.write_all(out.trim().as_bytes())?;
+            Ok(())
+        };
+        hb.register_helper("view_note", Box::new(view_note_helper));
+        let phantom = PhantomData;
+        Ok(NotesView { context, hb, phantom })
+    }
+
+    fn hb(&self) -> &Handlebars { &self.hb }
+    fn hb_mut(&mut self) -> &mut Handlebars { &mut self.hb }
+    fn context(&self) -> &BTreeMap<String, Value> { &self.context }
+
+    fn render(&mut self, input: &Self::Input) -> Result<String, ViewError> {
+        self.load_context(input)?;
+        let out = self.hb.render("template", &self.context)?;
+        if input.is_empty() && out.trim().is_empty() {
+            return Ok(String::new());
+        }
+        Ok(out)
+    }
+
+    fn load_context(&mut self, input: &Self::Input) -> Result<(), &'static str> {
+        let in_val = serde_json::to_value(&input.data).map_err(|_| "Could not parse notes into value")?;
+        self.context.insert("notes".to_string(), in_val);
+        Ok(())
+    }
+
+    fn template_name() -> &'static str { "notes" }
+}
+
+/// Lilypond commands for each of the voices, in order of appearance.
+static VOICE_COMMANDS: [&str; 4] = ["\\voiceOne", "\\voiceTwo", "\\voiceThree", "\\voiceFour"];
+
+impl<N, D> View for VoicesView<N, D>
+where D: 'static + Durational,
+      N: Note<D>
+{
+    type Input = Voices<N, D>;
+
+    fn new(source: Option<String>, context: BTreeMap<String, Value>) -> Result<Self, Box<dyn Error>> {
+        let hb: Handlebars = Self::init_handlebars(source)?;
+        let phantom = PhantomData;
+        Ok(VoicesView { context, hb, phantom })
+    }
+
+    fn hb(&self) -> &Handlebars { &self.hb }
+    fn hb_mut(&mut self) -> &mut Handlebars { &mut self.hb }
+    fn context(&self) -> &BTreeMap<String, Value> { &self.context }
+
+    fn load_context(&mut self, input: &Self::Input) -> Result<(), &'static str> {
+        let formatted = input.format()?;
+        if formatted.len() > VOICE_COMMANDS.len() {
+            return Err("Too many voices for a single staff");
+        }
+        let voices: Vec<Value> = formatted.iter().zip(VOICE_COMMANDS.iter())
+            .map(|(music, command)| json!({ "command": command, "music": music.trim() }))
+            .collect();
+        self.context.insert("voices".to_string(), Value::Array(voices));
+        Ok(())
+    }
+
+    fn template_name() -> &'static str { "voices" }
+}
+
+impl<D> View for ScoreView<D>
+where D: Durational
+{
+    type Input = Score<D>;
+
+    fn new(source: Option<String>, context: BTreeMap<String, Value>) -> Result<Self, Box<dyn Error>> {
+        let hb: Handlebars = Self::init_handlebars(source)?;
+        let phantom = PhantomData;
+        Ok(ScoreView { context, hb, phantom })
+    }
+
+    fn hb(&self) -> &Handlebars { &self.hb }
+    fn hb_mut(&mut self) -> &mut Handlebars { &mut self.hb }
+    fn context(&self) -> &BTreeMap<String, Value> { &self.context }
+
+    fn load_context(&mut self, input: &Self::Input) -> Result<(), &'static str> {
+        let in_val = serde_json::to_value(input).map_err(|_| "Could not parse score into value")?;
+        self.context.insert("score".to_string(), in_val);
+        Ok(())
+    }
+
+    fn template_name() -> &'static str { "score" }
+}
+
+impl<D> View for AbcView<D>
+where D: Durational
+{
+    type Input = Notes<SingleNote<ETPitch, D>>;
+
+    fn new(source: Option<String>, context: BTreeMap<String, Value>) -> Result<Self, Box<dyn Error>> {
+        let hb: Handlebars = Self::init_handlebars(source)?;
+        let phantom = PhantomData;
+        Ok(AbcView { context, hb, phantom })
+    }
+
+    fn hb(&self) -> &Handlebars { &self.hb }
+    fn hb_mut(&mut self) -> &mut Handlebars { &mut self.hb }
+    fn context(&self) -> &BTreeMap<String, Value> { &self.context }
+
+    fn load_context(&mut self, input: &Self::Input) -> Result<(), &'static str> {
+        let defaults = [("index", "1"), ("title", "Untitled"), ("meter", "4/4"), 
+                        ("unit_length", "1/8"), ("key", "C")];
+        for &(field, value) in defaults.iter() {
+            self.context.entry(field.to_string()).or_insert_with(|| json!(value));
+        }
+        let unit = self.context["unit_length"].as_str()
+            .and_then(|unit| {
+                let mut parts = unit.split('/').map(|part| part.trim().parse::<u32>());
+                match (parts.next(), parts.next()) {
+                    (Some(Ok(num)), Some(Ok(den))) if num > 0 && den > 0 => Some((num, den)),
+                    _ => None
+                }
+            })
+            .ok_or("Could not read unit_length")?;
+        let body = abc_body(input, unit);
+        self.context.insert("body".to_string(), json!(body));
+        Ok(())
+    }
+
+    fn template_name() -> &'static str { "abc" }
+}
+
+/// Writes each note as its ABC pitch followed by its length in multiples of `unit`. There are no
+/// bar lines in the body, so an accidental holds until the end of the tune: a note that follows
+/// an altered note of the same name is given an explicit natural.
+fn abc_body<D>(notes: &Notes<SingleNote<ETPitch, D>>, unit: (u32, u32)) -> String 
+where D: Durational
+{
+    let mut altered: HashMap<String, bool> = HashMap::new();
+    let formatted: Vec<String> = notes.data.iter().map(|note| {
+        let name = note.pitch().as_abc();
+        let letter = name.trim_start_matches(['^', '_']).to_string();
+        let is_altered = letter.len() < name.len();
+        let was_altered = altered.insert(letter, is_altered).unwrap_or(false);
+        let name = if was_altered && !is_altered { format!("={}", name) } else { name };
+
+        let (num, den) = note.duration().as_ratio();
+        let (num, den) = (num * unit.1, den * unit.0);
+        let least = gcd(num, den);
+        match (num / least, den / least) {
+            (1, 1) => name,
+            (num, 1) => format!("{}{}", name, num),
+            (1, den) => format!("{}/{}", name, den),
+            (num, den) => format!("{}{}/{}", name, num, den)
+        }
+    }).collect();
+    formatted.join(" ")
+}
+
+macro_rules! viewable {
+    ($item:tt, $view:tt) => {
+        impl<'a, P, D> Viewable<'a, D> for $item<P, D>
+        where D: 'a + Durational + Serialize,
+            P: Pitch + Clone + Serialize,
+            for<'de> D: Deserialize<'de>
+        {
+            type View = $view<P, D>;
+        }
+    };
+}
+
+viewable!(SingleNote, SingleNoteView);
+viewable!(Chord, ChordView);
+viewable!(NoteEvent, NoteEventView);
+
+impl<'a, D> Viewable<'a, D> for Rest<D>
+where D: 'a + Durational + Serialize,
+      for<'de> D: Deserialize<'de>
+{
+    type View = RestView<D>;
+}
+
+impl<'a, D, N> Viewable<'a, D> for Notes<N>
+where D: 'a + Durational + Serialize,
+      N: Note<D> + Clone + Serialize + Viewable<'a, D>,
+      for<'de> D: Deserialize<'de>,
+      for<'de> N: Deserialize<'de>
+{
+    type View = NotesView<N, D>;
+}
+
+impl<'a, D, N> Viewable<'a, D> for Voices<N, D>
+where D: 'static + Durational,
+      N: Note<D>
+{
+    type View = VoicesView<N, D>;
+}
+
+impl<'a, D> Viewable<'a, D> for Score<D>
+where D: 'a + Durational
+{
+    type View = ScoreView<D>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::*;
+    use super::super::notes::*;
+    use super::super::sequenza::*;
+    
+    fn initialize_notes() -> Vec<SingleNote<ETPitch, RatioDuration>> {
+        vec![
+            SingleNote::new(ETPitch::new(60), Duration(RatioDuration(1, 2))),
+            SingleNote::new(ETPitch::new(62), Duration(RatioDuration(1, 4))),
+            SingleNote::new(ETPitch::new(64), Duration(RatioDuration(1, 4))),
+            SingleNote::new(ETPitch::new(65), Duration(RatioDuration(1, 4)))
+        ]
+    }
+
+    #[test]
+    fn test_render_note_custom_template() {
+        let notes = initialize_notes();
+        let context = BTreeMap::new();
+        let mut view = SingleNoteView::new(
+            Some("{{ note.text }}".to_string()),
+            context).unwrap();
+
+        let out = notes[0].render(&mut view).unwrap();
+        assert_eq!("c", &out);
+    }
+
+    #[test]
+    fn test_render_note_template() {
+        let notes = initialize_notes();
+        let context = BTreeMap::new();
+        let mut view = View::new(None, context).unwrap();
+        let out = notes[0].render(&mut view).unwrap();
+        assert_eq!("c2\n", out);
+    }
+
+    #[test]
+    fn test_render_notes_template() {
+        let notes = Notes::new(initialize_notes());
+        let context = BTreeMap::new();
+        let mut view = View::new(None, context).unwrap();
+        let out = notes.render(&mut view).unwrap();
+        assert_eq!(" c2  d4  e4  f4 \n", out);
+    }
+
+    #[test]
+    fn test_render_notes_with_separator() {
+        let notes = Notes::new(initialize_notes());
+        let mut context = BTreeMap::new();
+        context.insert("separator".to_string(), json!("\n"));
+        let mut view = View::new(None, context).unwrap();
+        let out = notes.render(&mut view).unwrap();
+        assert_eq!("c2\nd4\ne4\nf4\n", out);
+
+        let mut context = BTreeMap::new();
+        context.insert("separator".to_string(), json!(" "));
+        let mut view = View::new(None, context).unwrap();
+        let out = notes.render(&mut view).unwrap();
+        assert_eq!("c2 d4 e4 f4\n", out);
+    }
+
+    #[test]
+    fn test_render_notes_with_chord_template() {
+        let notes = Notes::new(vec![
+                               Chord::new(vec![ETPitch::new(60), ETPitch::new(62)], 
+                                          RatioDuration(1, 2)),
+                               Chord::new(vec![ETPitch::new(64), ETPitch::new(65)], 
+                                          RatioDuration(1, 2))
+        ]);
+        let context = BTreeMap::new();
+        let mut view = View::new(None, context).unwrap();
         let out = notes.render(&mut view).unwrap();
         assert_eq!(" < c  d >2  < e  f >2 \n", &out);
     }
 
     #[test]
-    fn test_render_chord_template() {
-        let chord: Chord<ETPitch, RatioDuration> = Chord::new(vec![ETPitch::new(60), ETPitch::new(62)], Duration(RatioDuration(1, 2)));
+    fn test_render_chord_template() {
+        let chord: Chord<ETPitch, RatioDuration> = Chord::new(vec![ETPitch::new(60), ETPitch::new(62)], Duration(RatioDuration(1, 2)));
+        let mut view = View::new(None, BTreeMap::new()).unwrap();
+        let out = chord.render(&mut view).unwrap();
+        assert_eq!("< c  d >2\n", &out);
+    }
+
+    #[test]
+    fn test_total_duration() {
+        let notes = Notes::new(initialize_notes());
+        assert_eq!(notes.len(), 4);
+        assert_eq!(notes.total_duration(), Duration(RatioDuration(5, 4)));
+        let empty: Notes<SingleNote<ETPitch, RatioDuration>> = Notes::new(vec![]);
+        assert!(empty.is_empty());
+        assert_eq!(empty.total_duration(), Duration(RatioDuration(0, 1)));
+    }
+
+    #[test]
+    fn test_onsets() {
+        let notes = Notes::new(initialize_notes());
+        let ranges = notes.onsets();
+        let onsets: Vec<(u32, u32)> = ranges.iter().map(|&(onset, _)| onset.as_ratio()).collect();
+        assert_eq!(onsets, vec![(0, 1), (1, 2), (3, 4), (1, 1)]);
+        assert_eq!(ranges.last().unwrap().1, notes.total_duration());
+        for pair in ranges.windows(2) {
+            assert_eq!(pair[0].1, pair[1].0);
+        }
+    }
+
+    #[test]
+    fn test_render_keeps_spelling_and_language() {
+        let flats = Notes::from_lilypond("des4 ees4").unwrap();
+        assert_eq!(flats.render_default().unwrap(), " dflat4  eflat4 \n");
+
+        let deutsch: Notes<SingleNote<ETPitch, RatioDuration>> = Notes::new([70, 71].iter()
+            .map(|&midi| SingleNote::new(ETPitch::new(midi).in_language(NoteLanguage::Deutsch), (1, 4)))
+            .collect());
+        assert_eq!(deutsch.render_default().unwrap(), " b4  h4 \n");
+    }
+
+    #[test]
+    fn test_render_empty_notes() {
+        let empty: Notes<SingleNote<ETPitch, RatioDuration>> = Notes::new(vec![]);
+        assert_eq!(empty.render_default().unwrap(), "");
+
+        let mut view = NotesView::new(Some("{ {{#each notes as |n|}}{{view_note n}}{{/each}} }".to_string()), BTreeMap::new()).unwrap();
+        assert_eq!(empty.render(&mut view).unwrap(), "{  }");
+
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![Box::new(Measure::from_time_signature(4, 4))];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        assert_eq!(format_notes(&empty, &mut controller).unwrap(), "");
+        assert!(measurize(&empty, &mut controller).unwrap().is_empty());
+        assert_eq!(controller.measure_number(), 1);
+
+        let voices = Voices::new(vec![empty], Vec::new);
+        assert_eq!(voices.format().unwrap(), vec![String::new()]);
+    }
+
+    #[test]
+    fn test_malformed_note_error() {
+        // The template writes notes kept in the context alongside the passage, one of which is
+        // missing its duration
+        let good = serde_json::to_value(SingleNote::<ETPitch, RatioDuration>::new(60, (1, 4))).unwrap();
+        let mut context = BTreeMap::new();
+        context.insert("cue".to_string(), json!([good, { "pitch": { "midi": 62 } }]));
+        let template = "{{#each cue}}{{view_note this}} {{/each}}".to_string();
+        let mut view: NotesView<SingleNote<ETPitch, RatioDuration>, RatioDuration> = NotesView::new(Some(template), context).unwrap();
+        let error = Notes::new(initialize_notes()).render(&mut view).unwrap_err();
+        assert!(error.desc.starts_with("Could not read note at index 1: "), "{}", error.desc);
+        assert!(error.desc.contains("duration"), "{}", error.desc);
+    }
+
+    #[test]
+    fn test_serialized_score() {
+        let mut notes = Notes::from_lilypond("c4 d8 e").unwrap();
+        notes.add_span(SpanKind::Crescendo, 0..3).unwrap();
+        let json = SerializedScore::new(notes.clone()).to_json().unwrap();
+        let read: SerializedScore<SingleNote<ETPitch, RatioDuration>> = SerializedScore::from_json(&json).unwrap();
+        assert_eq!(read.version, SCORE_VERSION);
+        assert_eq!(read.notes.as_slice(), notes.as_slice());
+        assert_eq!(read.notes.spans(), notes.spans());
+
+        let future = json.replace(&format!("\"version\":{}", SCORE_VERSION), "\"version\":99");
+        assert_ne!(future, json);
+        assert_eq!(SerializedScore::<SingleNote<ETPitch, RatioDuration>>::from_json(&future).err(), 
+                   Some("Score was saved with an unknown version"));
+        assert!(SerializedScore::<SingleNote<ETPitch, RatioDuration>>::from_json("{\"notes\": []}").is_err());
+    }
+
+    #[test]
+    fn test_join_and_split() {
+        let first = Notes::from_lilypond("c4 d").unwrap();
+        let second = Notes::from_lilypond("e4 f").unwrap();
+        let joined = first.clone() + second.clone();
+        assert_eq!(joined.len(), 4);
+        assert_eq!(joined.render_default().unwrap(), " c4  d4  e4  f4 \n");
+
+        let (head, tail) = joined.split_at(1);
+        assert_eq!(head.len(), 1);
+        assert_eq!(tail.render_default().unwrap(), " d4  e4  f4 \n");
+
+        let mut spliced = first.clone();
+        spliced.insert_at(1, second.clone());
+        assert_eq!(spliced.render_default().unwrap(), " c4  e4  f4  d4 \n");
+
+        let mut appended = first.clone();
+        let mut rest = second.clone();
+        appended.append(&mut rest);
+        assert!(rest.is_empty());
+        appended.extend(second);
+        assert_eq!(appended.render_default().unwrap(), " c4  d4  e4  f4  e4  f4 \n");
+    }
+
+    #[test]
+    fn test_register_partial_with_hb_mut() {
+        let note: SingleNote<ETPitch, RatioDuration> = SingleNote::new(ETPitch::new(60), RatioDuration(1, 4));
+        let source = "{{ note.text }}{{ note.ly_duration }}{{> dynamics }}".to_string();
+        let mut view: SingleNoteView<ETPitch, RatioDuration> = View::new(Some(source), BTreeMap::new()).unwrap();
+        view.hb_mut().register_template_string("dynamics", "\\p").unwrap();
+        assert_eq!(note.render(&mut view).unwrap(), "c4\\p");
+    }
+
+    #[test]
+    fn test_register_partial() {
+        let notes = Notes::new(initialize_notes());
+        let mut context = BTreeMap::new();
+        context.insert("dynamic".to_string(), json!("\\mf"));
+        let source = "{{> dynamics }}{{#each notes as |note|}} {{ note.text }}{{/each}}".to_string();
+        let mut view: NotesView<SingleNote<ETPitch, RatioDuration>, RatioDuration> = View::new(Some(source), context).unwrap();
+        view.register_partial("dynamics", "{{ dynamic }}").unwrap();
+        assert_eq!(notes.render(&mut view).unwrap(), "\\mf c d e f");
+        assert!(view.register_partial("broken", "{{#if}}").is_err());
+    }
+
+    #[test]
+    fn test_notes_share_context() {
+        let notes = Notes::new(initialize_notes());
+        let mut context = BTreeMap::new();
+        context.insert("dynamic".to_string(), json!("\\p"));
+        context.insert("separator".to_string(), json!(" "));
+        let mut view: NotesView<SingleNote<ETPitch, RatioDuration>, RatioDuration> = View::new(None, context).unwrap();
+        view.register_partial("note", "{{ note.text }}{{ note.ly_duration }}{{ dynamic }}").unwrap();
+        assert_eq!(notes.render(&mut view).unwrap(), "c2\\p d4\\p e4\\p f4\\p\n");
+    }
+
+    #[test]
+    fn test_escape_function() {
+        let chord: Chord<ETPitch, RatioDuration> = Chord::new(vec![ETPitch::new(60), ETPitch::new(64)], RatioDuration(1, 2));
+        let source = "{{ chord.text }}{{ chord.ly_duration }}".to_string();
+        let mut raw: ChordView<ETPitch, RatioDuration> = View::new(Some(source.clone()), BTreeMap::new()).unwrap();
+        assert_eq!(chord.render(&mut raw).unwrap(), "<c e>2");
+
+        let mut escaped = ChordView::<ETPitch, RatioDuration>::new(Some(source), BTreeMap::new()).unwrap()
+            .with_escape(::handlebars::html_escape);
+        assert_eq!(chord.render(&mut escaped).unwrap(), "&lt;c e&gt;2");
+    }
+
+    #[test]
+    fn test_render_erased_views() {
+        let note: SingleNote<ETPitch, RatioDuration> = SingleNote::new(ETPitch::new(60), RatioDuration(1, 4));
+        let chord: Chord<ETPitch, RatioDuration> = Chord::new(vec![ETPitch::new(60), ETPitch::new(64)], RatioDuration(1, 2));
+        let mut views: Vec<Box<dyn ErasedView>> = vec![
+            Box::new(SingleNoteView::<ETPitch, RatioDuration>::new(None, BTreeMap::new()).unwrap()),
+            Box::new(ChordView::<ETPitch, RatioDuration>::new(None, BTreeMap::new()).unwrap())
+        ];
+        let inputs = [serde_json::to_value(&note).unwrap(), serde_json::to_value(&chord).unwrap()];
+        let out: Vec<String> = views.iter_mut().zip(inputs.iter())
+            .map(|(view, input)| view.render_value(input).unwrap())
+            .collect();
+        assert_eq!(out, vec!["c4\n", "< c  e >2\n"]);
+        assert!(views[1].render_value(&json!({ "duration": 1 })).is_err());
+    }
+
+    #[test]
+    fn test_render_mixed_passage() {
+        let passage: Notes<NoteEvent<ETPitch, RatioDuration>> = NotesBuilder::default()
+            .note(60, (1, 4))
+            .chord(&[64, 67], (1, 4))
+            .rest((1, 2))
+            .build();
+        let out = passage.render_default().unwrap();
+        assert_eq!(" c4  < e  g >4  r2 \n", &out);
+
+        let json = serde_json::to_value(&passage.data[1]).unwrap();
+        assert_eq!(json["type"], json!("Chord"));
+
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Beat::new_ratio(1, 2)),
+            Box::new(Beat::new_ratio(1, 2))
+        ];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        assert_eq!(format_notes(&passage, &mut controller), Ok("c4 <e g>4 r2".to_string()));
+    }
+
+    #[test]
+    fn test_render_fingering() {
+        let note: SingleNote<ETPitch, RatioDuration> = SingleNote::new(60, RatioDuration(1, 4))
+            .with_fingering(3);
+        assert_eq!("c4-3\n", &note.render_default().unwrap());
+
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Beat::new_ratio(1, 8)),
+            Box::new(Beat::new_ratio(1, 8))
+        ];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        assert_eq!(format_note(&note, &mut controller), Ok("c8-3 ~ c8".to_string()));
+    }
+
+    #[test]
+    fn test_tie_chord_across_barline() {
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Measure::from_time_signature(2, 4)),
+            Box::new(Measure::from_time_signature(2, 4))
+        ];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        let passage: Notes<NoteEvent<ETPitch, RatioDuration>> = NotesBuilder::default()
+            .note(62, (1, 4))
+            .chord(&[60, 64, 67], (1, 2))
+            .note(62, (1, 4))
+            .build();
+        let out = format_notes(&passage, &mut controller).unwrap();
+        assert_eq!(" %m. 1\n d4 <c e g>4 ~  |\n  %m. 2\n <c e g>4 d4 |\n ", &out);
+    }
+
+    #[test]
+    fn test_tie_note_across_several_measures() {
+        let whole_measure = || -> Box<dyn Grouping<RatioDuration>> {
+            Box::new(Measure::from_contents(vec![Box::new(Beat::new_ratio(1, 1))]))
+        };
+        let groupings = vec![whole_measure(), whole_measure(), whole_measure()];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        let passage = Notes::new(vec![
+            SingleNote::<ETPitch, RatioDuration>::new(ETPitch::new(62), RatioDuration(1, 4)),
+            SingleNote::new(ETPitch::new(60), RatioDuration(2, 1)),
+            SingleNote::new(ETPitch::new(64), RatioDuration(3, 4))
+        ]);
+        let out = format_notes(&passage, &mut controller).unwrap();
+        assert_eq!(" %m. 1\n d4 c2. ~  |\n  %m. 2\n c1 ~  |\n  %m. 3\n c4 e2. |\n ", &out);
+    }
+
+    #[test]
+    fn test_output_styles() {
+        let groupings = || -> Box<dyn Iterator<Item=Box<dyn Grouping<RatioDuration>>>> {
+            let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+                Box::new(Measure::from_time_signature(2, 4)),
+                Box::new(Measure::from_time_signature(2, 4))
+            ];
+            Box::new(groupings.into_iter())
+        };
+        let passage = Notes::from_lilypond("d4 c2 e4").unwrap();
+
+        let mut pretty = GroupingController::new(groupings()).unwrap();
+        assert_eq!(pretty.style(), OutputStyle::Pretty);
+        assert_eq!(format_notes(&passage, &mut pretty).unwrap(), 
+                   " %m. 1\n d4 c4 ~  |\n  %m. 2\n c4 e4 |\n ");
+
+        let mut compact = GroupingController::new(groupings()).unwrap().with_style(OutputStyle::Compact);
+        assert_eq!(format_notes(&passage, &mut compact).unwrap(), "d4 c4 ~ | c4 e4 |");
+    }
+
+    #[test]
+    fn test_bar_checks() {
+        let groupings = || -> Box<dyn Iterator<Item=Box<dyn Grouping<RatioDuration>>>> {
+            let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+                Box::new(Measure::from_time_signature(2, 4).with_barline(BarlineType::Double)),
+                Box::new(Measure::from_time_signature(2, 4).with_barline(BarlineType::Final))
+            ];
+            Box::new(groupings.into_iter())
+        };
+        let passage = Notes::from_lilypond("d4 c2 e4").unwrap();
+
+        let mut unchecked = GroupingController::new(groupings()).unwrap();
+        assert!(!unchecked.bar_checks());
+        assert_eq!(format_notes(&passage, &mut unchecked).unwrap(), 
+                   " %m. 1\n d4 c4 ~  \\bar \"||\"\n  %m. 2\n c4 e4 \\bar \"|.\"\n ");
+
+        let mut checked = GroupingController::new(groupings()).unwrap().with_bar_checks(true);
+        assert_eq!(format_notes(&passage, &mut checked).unwrap(), 
+                   " %m. 1\n d4 c4 ~  \\bar \"||\" |\n  %m. 2\n c4 e4 \\bar \"|.\" |\n ");
+
+        // A single barline is a bar check already
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Measure::from_time_signature(2, 4)),
+            Box::new(Measure::from_time_signature(2, 4))
+        ];
+        let mut checked = GroupingController::new(Box::new(groupings.into_iter())).unwrap()
+            .with_bar_checks(true)
+            .with_style(OutputStyle::Compact);
+        assert_eq!(format_notes(&passage, &mut checked).unwrap(), "d4 c4 ~ | c4 e4 |");
+    }
+
+    #[test]
+    fn test_custom_tie() {
+        let passage: Notes<SingleNote<ETPitch, RatioDuration>> = NotesBuilder::new()
+            .notes(&[(62, (1, 4)), (60, (1, 2)), (64, (1, 4))])
+            .build();
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Measure::from_time_signature(2, 4)),
+            Box::new(Measure::from_time_signature(2, 4))
+        ];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap()
+            .with_tie("~")
+            .with_style(OutputStyle::Compact);
+        assert_eq!(controller.tie(), "~");
+        assert_eq!(format_notes(&passage, &mut controller).unwrap(), "d4 c4~ | c4 e4 |");
+    }
+
+    #[test]
+    fn test_line_breaks() {
+        let passage = Notes::from_lilypond("c4 d e f g a b c'2 c'4").unwrap();
+        let groupings = || -> Box<dyn Iterator<Item=Box<dyn Grouping<RatioDuration>>>> {
+            Box::new((0..5).map(|_| Box::new(Measure::from_time_signature(2, 4)) as Box<dyn Grouping<RatioDuration>>))
+        };
+        let mut controller = GroupingController::new(groupings()).unwrap()
+            .with_line_breaks(2)
+            .with_style(OutputStyle::Compact);
+        assert_eq!(controller.line_breaks(), Some(2));
+        assert_eq!(format_notes(&passage, &mut controller).unwrap(), 
+                   "c4 d4 | e4 f4 |\ng4 a4 | b4 c'4 ~ |\nc'4 c'4 |");
+
+        let mut unbroken = GroupingController::new(groupings()).unwrap()
+            .with_line_breaks(0)
+            .with_style(OutputStyle::Compact);
+        assert_eq!(unbroken.line_breaks(), None);
+        assert!(!format_notes(&passage, &mut unbroken).unwrap().contains('\n'));
+    }
+
+    #[test]
+    fn test_glissando() {
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Beat::new_ratio(1, 4)),
+            Box::new(Beat::new_ratio(1, 2))
+        ];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        let passage = Notes::new(vec![
+            SingleNote::<ETPitch, RatioDuration>::new(ETPitch::new(60), RatioDuration(1, 2)).with_glissando(),
+            SingleNote::new(ETPitch::new(67), RatioDuration(1, 4))
+        ]);
+        assert_eq!(format_notes(&passage, &mut controller).unwrap(), "c4 ~ c4\\glissando g4");
+
+        let note = passage.as_slice()[0].clone().with_stem(StemDirection::Up);
+        assert_eq!(note.render_default().unwrap(), "\\stemUp c2\\glissando \\stemNeutral\n");
+    }
+
+    #[test]
+    fn test_grace_note() {
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Measure::from_time_signature(2, 4)),
+            Box::new(Measure::from_time_signature(2, 4))
+        ];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        let passage = Notes::new(vec![
+            SingleNote::<ETPitch, RatioDuration>::new(ETPitch::new(60), RatioDuration(1, 4)),
+            SingleNote::grace(ETPitch::new(65), RatioDuration(1, 16)),
+            SingleNote::new(ETPitch::new(64), RatioDuration(1, 4)),
+            SingleNote::grace(ETPitch::new(67), RatioDuration(1, 8)),
+            SingleNote::new(ETPitch::new(65), RatioDuration(1, 2))
+        ]);
+        assert_eq!(passage.total_duration(), Duration(RatioDuration(1, 1)));
+        let out = format_notes(&passage, &mut controller).unwrap();
+        assert_eq!(" %m. 1\n c4 \\grace { f16 } e4 |\n  \\grace { g8 }  %m. 2\n f4 ~ f4 |\n ", &out);
+        assert_eq!(passage.as_slice()[1].render_default().unwrap(), "\\grace { f16 }\n");
+    }
+
+    #[test]
+    fn test_beam_closes_before_tie() {
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Beat::new_ratio(1, 4).beamed()),
+            Box::new(Beat::new_ratio(1, 4).beamed()),
+            Box::new(Beat::new_ratio(1, 4).beamed())
+        ];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        let passage = Notes::from_lilypond("c8 c4 c8 c4").unwrap();
+        assert_eq!(format_notes(&passage, &mut controller).unwrap(), "c8[ c8] ~ c8[ c8] c4");
+    }
+
+    #[test]
+    fn test_hairpins() {
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![Box::new(Measure::from_time_signature(4, 4))];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap()
+            .with_style(OutputStyle::Compact);
+        let mut passage = Notes::from_lilypond("c4 d e f").unwrap();
+        passage.add_span(SpanKind::Crescendo, 0..2).unwrap();
+        passage.add_span(SpanKind::Decrescendo, 2..4).unwrap();
+        assert_eq!(format_notes(&passage, &mut controller).unwrap(), "c4\\< d4\\! e4\\> f4\\! |");
+
+        assert!(passage.add_span(SpanKind::Crescendo, 1..3).is_err());
+        assert!(passage.add_span(SpanKind::Crescendo, 3..5).is_err());
+        assert!(passage.add_span(SpanKind::Crescendo, 3..4).is_err());
+
+        // Spans follow their notes when passages are joined and split
+        let joined = Notes::from_lilypond("g4").unwrap() + passage;
+        assert_eq!(joined.spans()[0].notes, 1..3);
+        let (head, tail) = joined.split_at(2);
+        assert!(head.spans().is_empty());
+        assert_eq!(tail.spans(), &[Span { kind: SpanKind::Decrescendo, notes: 1..3 }]);
+
+        // An empty range, as read from a hand-written file, ends on no note at all
+        let mut passage = Notes::from_lilypond("c4").unwrap();
+        passage.spans.push(Span { kind: SpanKind::Crescendo, notes: 0..0 });
+        assert_eq!(passage.span_marks(0), SpanKind::Crescendo.start());
+    }
+
+    #[test]
+    fn test_text_span() {
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![Box::new(Measure::from_time_signature(4, 4))];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap()
+            .with_style(OutputStyle::Compact);
+        let mut passage = Notes::from_lilypond("c4 d e f").unwrap();
+        passage.add_span(SpanKind::TextSpan(Markup::new("poco a poco cresc.").italic().below()), 0..3).unwrap();
+        // Text spans may overlap hairpins
+        passage.add_span(SpanKind::Crescendo, 0..4).unwrap();
+        assert_eq!(format_notes(&passage, &mut controller).unwrap(), 
+                   "c4_\\tweak bound-details.left.text \\markup { \\italic poco a poco cresc. } \\startTextSpan\\< \
+                    d4 e4\\stopTextSpan f4\\! |");
+    }
+
+    #[test]
+    fn test_clef_changes() {
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![Box::new(Measure::from_time_signature(4, 4))];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap()
+            .with_style(OutputStyle::Compact);
+        let melody = Notes::from_lilypond("e4 c a, f,").unwrap();
+        assert_eq!(melody.clef_changes(60), vec![(2, Clef::Bass)]);
+        assert_eq!(format_notes_with_clefs(&melody, 60, &mut controller).unwrap(), 
+                   "e4 c4 \\clef bass a,4 f,4 |");
+
+        let melody = Notes::from_lilypond("g,4 c e").unwrap();
+        assert_eq!(melody.clef_changes(60), vec![(0, Clef::Bass), (1, Clef::Treble)]);
+        assert!(Notes::from_lilypond("c4 e g").unwrap().clef_changes(60).is_empty());
+    }
+
+    #[test]
+    fn test_chromatic() {
+        let scale = Notes::chromatic(ETPitch::new(60), 13, (1, 8));
+        let notes = scale.as_slice();
+        assert_eq!(notes.len(), 13);
+        let midi: Vec<u32> = notes.iter().map(|note| note.pitch().midi).collect();
+        assert_eq!(midi, (60..73).collect::<Vec<u32>>());
+        assert!(notes.iter().all(|note| note.duration() == Duration::new(1, 8)));
+        assert_eq!(notes[1].pitch().pitch(), "csharp");
+        assert_eq!(notes[12].pitch().pitch(), "c'");
+        assert!(Notes::chromatic(ETPitch::new(60), 0, (1, 4)).is_empty());
+    }
+
+    #[test]
+    fn test_split_into_printable_values() {
+        let groupings = || -> Box<dyn Iterator<Item=Box<dyn Grouping<RatioDuration>>>> {
+            let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+                Box::new(Beat::new_ratio(1, 2)),
+                Box::new(Beat::new_ratio(1, 2))
+            ];
+            Box::new(groupings.into_iter())
+        };
+        // The rest of the first beat is a dotted quarter, written as a single note
+        let mut controller = GroupingController::new(groupings()).unwrap();
+        let passage = Notes::from_lilypond("c8 d2 e8").unwrap();
+        assert_eq!(format_notes(&passage, &mut controller).unwrap(), "c8 d4. ~ d8 e8");
+
+        // A 5/8 note inside of a whole-note beat is a half note tied to an eighth
+        let mut controller = GroupingController::new(Box::new(vec![Box::new(Beat::new_ratio(1, 1)) as Box<dyn Grouping<RatioDuration>>].into_iter())).unwrap();
+        let passage = Notes::new(vec![
+            SingleNote::<ETPitch, RatioDuration>::new(60, RatioDuration(1, 8)),
+            SingleNote::new(62, RatioDuration(5, 8)),
+            SingleNote::new(64, RatioDuration(1, 4))
+        ]);
+        assert_eq!(format_notes(&passage, &mut controller).unwrap(), "c8 d2 ~ d8 e4");
+
+        let mut controller = GroupingController::new(Box::new(vec![Box::new(Beat::new_ratio(1, 1)) as Box<dyn Grouping<RatioDuration>>].into_iter())).unwrap();
+        let passage = Notes::new(vec![SingleNote::<ETPitch, RatioDuration>::new(60, RatioDuration(7, 8))]);
+        let measures = measurize(&passage, &mut controller).unwrap();
+        let texts: Vec<&str> = measures[0].notes.iter().map(|note| note.text.as_str()).collect();
+        assert_eq!(texts, vec!["c2.", "c8"]);
+    }
+
+    #[test]
+    fn test_rest_across_barline() {
+        let groupings = || -> Box<dyn Iterator<Item=Box<dyn Grouping<RatioDuration>>>> {
+            let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+                Box::new(Measure::from_time_signature(3, 4)),
+                Box::new(Measure::from_time_signature(3, 4))
+            ];
+            Box::new(groupings.into_iter())
+        };
+        let passage: Notes<NoteEvent<ETPitch, RatioDuration>> = NotesBuilder::default()
+            .note(60, (1, 4))
+            .rest((5, 8))
+            .note(62, (1, 8))
+            .note(64, (1, 2))
+            .build();
+        let mut controller = GroupingController::new(groupings()).unwrap().with_style(OutputStyle::Compact);
+        assert_eq!(format_notes(&passage, &mut controller).unwrap(), "c4 r4 r4 | r8 d8 e4 ~ e4 |");
+
+        let mut controller = GroupingController::new(groupings()).unwrap();
+        let measures = measurize(&passage, &mut controller).unwrap();
+        assert!(measures.iter().flat_map(|measure| measure.notes.iter()).all(|note| !note.text.starts_with('r') || !note.tied));
+    }
+
+    #[test]
+    fn test_measurize() {
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Measure::from_time_signature(2, 4)),
+            Box::new(Measure::from_time_signature(2, 4))
+        ];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        let notes = Notes::new(vec![
+            SingleNote::<ETPitch, RatioDuration>::new(60, RatioDuration(1, 4)),
+            SingleNote::new(62, RatioDuration(1, 2)),
+            SingleNote::new(64, RatioDuration(1, 4))
+        ]);
+        let measures = measurize(&notes, &mut controller).unwrap();
+        let note = |start: &str, text: &str, tied, end: &str| RenderedNote { 
+            start: start.to_string(), 
+            text: text.to_string(), 
+            tied, 
+            tie: if tied { " ~ ".to_string() } else { String::new() }, 
+            end: end.to_string()
+        };
+        assert_eq!(measures, vec![
+            RenderedMeasure { index: 1, notes: vec![note(" %m. 1\n ", "c4", false, ""), note(" ", "d4", true, " |\n ")] },
+            RenderedMeasure { index: 2, notes: vec![note(" %m. 2\n ", "d4", false, ""), note(" ", "e4", false, " |\n ")] }
+        ]);
+    }
+
+    /// A passage with a repeat, alternative endings, a tuplet and a tie across a barline, laid
+    /// out against a fresh set of groupings each time it is called.
+    type Passage = (Notes<SingleNote<ETPitch, RatioDuration>>, Vec<Box<dyn Grouping<RatioDuration>>>);
+
+    fn repeated_passage() -> Passage {
+        let measure = || -> Box<dyn Grouping<RatioDuration>> { Box::new(Measure::from_time_signature(2, 4)) };
+        let triplet: Box<dyn Grouping<RatioDuration>> = Box::new(Measure::from_contents(vec![
+            Box::new(Tuplet::new((3, 2), vec![
+                Box::new(Beat::new_ratio(1, 12)) as Box<dyn Grouping<RatioDuration>>,
+                Box::new(Beat::new_ratio(1, 12)),
+                Box::new(Beat::new_ratio(1, 12))
+            ])),
+            Box::new(Beat::new_ratio(1, 4))
+        ]).with_barline(BarlineType::Final));
+        let groupings = vec![
+            Box::new(Repeat::volta(2, vec![measure(), measure()]).with_alternatives(vec![measure(), measure()])) as Box<dyn Grouping<RatioDuration>>,
+            triplet
+        ];
+        let notes = Notes::new(vec![
+            SingleNote::new(60, (1, 4)),
+            SingleNote::new(62, (1, 2)),
+            SingleNote::new(64, (1, 4)),
+            SingleNote::new(65, (1, 4)),
+            SingleNote::new(67, (1, 4)),
+            SingleNote::new(69, (1, 2)),
+            SingleNote::new(71, (1, 12)),
+            SingleNote::new(72, (1, 12)),
+            SingleNote::new(74, (1, 12)),
+            SingleNote::new(76, (1, 4))
+        ]);
+        (notes, groupings)
+    }
+
+    #[test]
+    fn test_render_measures() {
+        for &style in [OutputStyle::Pretty, OutputStyle::Compact].iter() {
+            let (notes, groupings) = repeated_passage();
+            let mut controller = GroupingController::from_vec(groupings).unwrap().with_style(style).with_bar_checks(true);
+            let measures = measurize(&notes, &mut controller).unwrap();
+            assert_eq!(measures.len(), 5);
+            let (notes, groupings) = repeated_passage();
+            let mut controller = GroupingController::from_vec(groupings).unwrap().with_style(style).with_bar_checks(true);
+            assert_eq!(render_measures(&measures, style).unwrap(), format_notes(&notes, &mut controller).unwrap());
+        }
+
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Measure::from_time_signature(2, 4)),
+            Box::new(Measure::from_time_signature(2, 4))
+        ];
+        let mut controller = GroupingController::from_vec(groupings).unwrap().with_tie("~").with_style(OutputStyle::Compact);
+        let notes = Notes::from_lilypond("c4 d2 e4").unwrap();
+        let measures = measurize(&notes, &mut controller).unwrap();
+        assert_eq!(measures[0].notes[1].tie, "~");
+        assert_eq!(render_measures(&measures, OutputStyle::Compact).unwrap(), "c4 d4~ | d4 e4 |");
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_render_parallel() {
+        let (notes, groupings) = repeated_passage();
+        let mut controller = GroupingController::from_vec(groupings).unwrap().with_style(OutputStyle::Compact);
+        let measures = measurize(&notes, &mut controller).unwrap();
+        let (notes, groupings) = repeated_passage();
+        let mut controller = GroupingController::from_vec(groupings).unwrap().with_style(OutputStyle::Compact);
+        let sequential = format_notes(&notes, &mut controller).unwrap();
+        assert!(sequential.contains("\\alternative"));
+        assert_eq!(render_parallel(&measures, OutputStyle::Compact).unwrap(), sequential);
+    }
+
+    #[test]
+    fn test_measurize_matches_format_note() {
+        let groupings = || -> Box<dyn Iterator<Item=Box<dyn Grouping<RatioDuration>>>> {
+            Box::new(vec![
+                Box::new(Beat::new_ratio(1, 4).beamed()) as Box<dyn Grouping<RatioDuration>>,
+                Box::new(Beat::new_ratio(1, 4))
+            ].into_iter())
+        };
+        let notes = Notes::new(vec![
+            SingleNote::<ETPitch, RatioDuration>::grace(67, (1, 16)),
+            SingleNote::new(60, (1, 8)),
+            SingleNote::new(62, (1, 8)),
+            SingleNote::new(64, (1, 4))
+        ]);
+        let mut controller = GroupingController::new(groupings()).unwrap();
+        let texts: Vec<String> = measurize(&notes, &mut controller).unwrap().into_iter()
+            .flat_map(|measure| measure.notes.into_iter().map(|note| note.text + &note.end))
+            .collect();
+        let mut controller = GroupingController::new(groupings()).unwrap();
+        assert_eq!(format_notes(&notes, &mut controller).unwrap(), texts.join(" "));
+        assert_eq!(texts, vec!["\\grace { g16 }", "c8[", "d8]", "e4"]);
+
+        // A note that may not be tied can't be split, whichever way it is laid out
+        let notes = Notes::new(vec![
+            SingleNote::<ETPitch, RatioDuration>::new(60, (1, 8)),
+            SingleNote::new(62, (1, 4)).with_tie(TiePolicy::Never)
+        ]);
+        let mut controller = GroupingController::new(groupings()).unwrap();
+        assert!(measurize(&notes, &mut controller).is_err());
+    }
+
+    #[test]
+    fn test_default_templates_are_embedded() {
+        // Every default template is found in the embedded table, rather than on disk
+        let templates = default_templates();
+        let names = [
+            SingleNoteView::<ETPitch, RatioDuration>::template_name(),
+            ChordView::<ETPitch, RatioDuration>::template_name(),
+            RestView::<RatioDuration>::template_name(),
+            NoteEventView::<ETPitch, RatioDuration>::template_name(),
+            NotesView::<SingleNote<ETPitch, RatioDuration>, RatioDuration>::template_name(),
+            VoicesView::<SingleNote<ETPitch, RatioDuration>, RatioDuration>::template_name(),
+            ScoreView::<RatioDuration>::template_name(),
+            AbcView::<RatioDuration>::template_name()
+        ];
+        for name in names.iter() {
+            assert!(templates.get(name).is_some_and(|source| !source.is_empty()), "no template for {}", name);
+        }
+
+        let note: SingleNote<ETPitch, RatioDuration> = SingleNote::new(60, RatioDuration(1, 4));
+        assert_eq!(note.render_default(), Ok("c4\n".to_string()));
+        assert!(Notes::new(initialize_notes()).render_default().is_ok());
+    }
+
+    #[test]
+    fn test_render_from_template_file() {
+        let note: SingleNote<ETPitch, RatioDuration> = SingleNote::new(60, RatioDuration(1, 2));
+        assert_eq!(note.render_default(), Ok("c2\n".to_string()));
+
+        let path = ::std::env::temp_dir().join("scritto_test_single_note.hbs");
+        fs::write(&path, "{{ note.text }}-{{ note.ly_duration }}").unwrap();
+        let mut view = SingleNoteView::from_template_file(&path, BTreeMap::new()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(note.render(&mut view), Ok("c-2".to_string()));
+        assert!(SingleNoteView::<ETPitch, RatioDuration>::from_template_file(&path, BTreeMap::new()).is_err());
+    }
+
+    #[test]
+    fn test_render_markup() {
+        let note: SingleNote<ETPitch, RatioDuration> = SingleNote::new(60, RatioDuration(1, 4))
+            .with_markup(Markup::new("dolce").italic());
+        assert_eq!("c4^\\markup { \\italic dolce }\n", &note.render_default().unwrap());
+
+        let json = serde_json::to_string(&note).unwrap();
+        let read: SingleNote<ETPitch, RatioDuration> = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, note);
+    }
+
+    #[test]
+    fn test_render_annotation() {
+        let note: SingleNote<ETPitch, RatioDuration> = SingleNote::new(60, RatioDuration(1, 4))
+            .with_annotation("^\"pizz.\"");
+        assert_eq!("c4^\"pizz.\"\n", &note.render_default().unwrap());
+
+        let note = note.with_fingering(2).with_markup(Markup::new("dolce").below());
+        assert_eq!("c4-2_\\markup { dolce }^\"pizz.\"\n", &note.render_default().unwrap());
+
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Beat::new_ratio(1, 8)),
+            Box::new(Beat::new_ratio(1, 8))
+        ];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        assert_eq!(format_note(&note, &mut controller), Ok("c8-2_\\markup { dolce }^\"pizz.\" ~ c8".to_string()));
+    }
+
+    #[test]
+    fn test_chord_dynamic_and_articulation() {
+        let chord: Chord<ETPitch, RatioDuration> = Chord::major(ETPitch::new(60), RatioDuration(1, 4))
+            .with_dynamic(Dynamic::F)
+            .with_articulation(Articulation::Accent);
+        assert_eq!(chord.render_default().unwrap(), "< c  e  g >4\\f->\n");
+
+        let json = serde_json::to_string(&chord).unwrap();
+        let read: Chord<ETPitch, RatioDuration> = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, chord);
+
+        let note: SingleNote<ETPitch, RatioDuration> = SingleNote::new(60, RatioDuration(1, 4))
+            .with_dynamic(Dynamic::Pp)
+            .with_articulation(Articulation::Tenuto)
+            .with_fingering(1);
+        assert_eq!(note.render_default().unwrap(), "c4\\pp---1\n");
+    }
+
+    #[test]
+    fn test_stem_directions() {
+        let notes = Notes::new(vec![
+            SingleNote::<ETPitch, RatioDuration>::new(60, RatioDuration(1, 4)).with_stem(StemDirection::Up),
+            SingleNote::new(62, RatioDuration(1, 2)).with_stem(StemDirection::Down),
+            SingleNote::new(64, RatioDuration(1, 4))
+        ]);
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Beat::new_ratio(1, 2)),
+            Box::new(Beat::new_ratio(1, 2))
+        ];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        assert_eq!(format_notes(&notes, &mut controller), 
+                   Ok("\\stemUp c4 \\stemNeutral \\stemDown d4 ~ d4 \\stemNeutral e4".to_string()));
+
+        let chord: Chord<ETPitch, RatioDuration> = Chord::new(vec![ETPitch::new(60), ETPitch::new(64)], RatioDuration(1, 4))
+            .with_stem(StemDirection::Down);
+        assert_eq!("\\stemDown < c  e >4 \\stemNeutral\n", &chord.render_default().unwrap());
+    }
+
+    #[test]
+    fn test_render_arpeggiated_chord() {
+        let chord: Chord<ETPitch, RatioDuration> = Chord::new(vec![ETPitch::new(60), ETPitch::new(64), ETPitch::new(67)], 
+                                                              RatioDuration(1, 2))
+            .invert()
+            .arpeggiated();
         let mut view = View::new(None, BTreeMap::new()).unwrap();
         let out = chord.render(&mut view).unwrap();
-        assert_eq!("< c  d >2\n", &out);
+        assert_eq!("< e  g  c' >2\\arpeggio\n", &out);
+
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Beat::new_ratio(1, 4)),
+            Box::new(Beat::new_ratio(1, 4))
+        ];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        assert_eq!(format_note(&chord, &mut controller), Ok("<e g c'>4\\arpeggio ~ <e g c'>4".to_string()));
+    }
+
+    #[test]
+    fn test_quantize_to_grid() {
+        let mut notes = Notes::new(vec![
+            SingleNote::<ETPitch, RatioDuration>::new(ETPitch::new(60), RatioDuration(65, 256)),
+            SingleNote::new(ETPitch::new(62), RatioDuration(63, 256)),
+            SingleNote::new(ETPitch::new(64), RatioDuration(33, 128)),
+            SingleNote::new(ETPitch::new(65), RatioDuration(31, 128))
+        ]);
+        notes.quantize_to_grid(Duration(RatioDuration(1, 16)));
+        for note in notes.data.iter() {
+            assert_eq!(note.duration(), Duration(RatioDuration(1, 4)));
+        }
+    }
+
+    #[test]
+    fn test_render_voices_template() {
+        let upper = Notes::new(vec![
+            SingleNote::<ETPitch, RatioDuration>::new(ETPitch::new(72), RatioDuration(1, 4)),
+            SingleNote::new(ETPitch::new(74), RatioDuration(1, 2)),
+            SingleNote::new(ETPitch::new(76), RatioDuration(1, 4))
+        ]);
+        let lower = Notes::new(vec![
+            SingleNote::new(ETPitch::new(60), RatioDuration(1, 2)),
+            SingleNote::new(ETPitch::new(59), RatioDuration(1, 2))
+        ]);
+        let voices = Voices::new(vec![upper, lower], || {
+            let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+                Box::new(Beat::new_ratio(1, 2)),
+                Box::new(Beat::new_ratio(1, 2))
+            ];
+            groupings
+        });
+        let mut view = View::new(None, BTreeMap::new()).unwrap();
+        let out = voices.render(&mut view).unwrap();
+        assert_eq!("<< { \\voiceOne c'4 d'4 ~ d'4 e'4 } \\\\ { \\voiceTwo c2 b,2 } >>\n", &out);
+    }
+
+    #[test]
+    fn test_buffered_format_matches_joined() {
+        let notes = Notes::new(initialize_notes());
+        let groupings = || {
+            let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+                Box::new(Measure::from_contents(vec![
+                    Box::new(Beat::new_ratio(1, 2)),
+                    Box::new(Beat::new_ratio(1, 2))
+                ])),
+                Box::new(Measure::from_contents(vec![
+                    Box::new(Beat::new_ratio(1, 2)),
+                    Box::new(Beat::new_ratio(1, 2))
+                ]))
+            ];
+            GroupingController::new(Box::new(groupings.into_iter())).unwrap()
+        };
+
+        let mut controller = groupings();
+        let joined = notes.data.iter()
+            .map(|note| format_note(note, &mut controller).unwrap())
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        let mut buffer = String::from("% melody\n");
+        format_notes_into(&notes, &mut groupings(), &mut buffer).unwrap();
+        assert_eq!(buffer, format!("% melody\n{}", joined));
+    }
+
+    #[test]
+    fn test_tie_policies() {
+        let format = |notes: Vec<SingleNote<ETPitch, RatioDuration>>| {
+            let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+                Box::new(Beat::new_ratio(1, 2)),
+                Box::new(Beat::new_ratio(1, 2))
+            ];
+            let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+            format_notes(&Notes::new(notes), &mut controller)
+        };
+
+        let auto = format(vec![
+            SingleNote::new(60, RatioDuration(1, 4)),
+            SingleNote::new(62, RatioDuration(1, 2)),
+            SingleNote::new(64, RatioDuration(1, 4))
+        ]);
+        assert_eq!(auto, Ok("c4 d4 ~ d4 e4".to_string()));
+
+        let forced = format(vec![
+            SingleNote::new(60, RatioDuration(1, 4)).with_tie(TiePolicy::Force),
+            SingleNote::new(60, RatioDuration(1, 4)),
+            SingleNote::new(62, RatioDuration(1, 2))
+        ]);
+        assert_eq!(forced, Ok("c4 ~ c4 d2".to_string()));
+
+        let never = format(vec![
+            SingleNote::new(60, RatioDuration(1, 4)),
+            SingleNote::new(62, RatioDuration(1, 2)).with_tie(TiePolicy::Never),
+            SingleNote::new(64, RatioDuration(1, 4))
+        ]);
+        assert!(never.is_err());
+    }
+
+    #[test]
+    fn test_render_abc_scale() {
+        let scale = Notes::new([60, 62, 64, 65, 67, 69, 71, 72].iter()
+            .map(|&midi| SingleNote::new(midi, RatioDuration(1, 4)))
+            .collect());
+        let mut context = BTreeMap::new();
+        context.insert("title".to_string(), json!("C major"));
+        let mut view = AbcView::new(None, context).unwrap();
+        let out = view.render(&scale).unwrap();
+        assert_eq!("X:1\nT:C major\nM:4/4\nL:1/8\nK:C\nC2 D2 E2 F2 G2 A2 B2 c2\n", &out);
+    }
+
+    #[test]
+    fn test_render_abc_lengths_and_accidentals() {
+        let notes = Notes::new(vec![
+            SingleNote::new(66, RatioDuration(1, 8)),
+            SingleNote::new(65, RatioDuration(3, 16)),
+            SingleNote::new(84, RatioDuration(1, 16)),
+            SingleNote::new(59, RatioDuration(1, 1))
+        ]);
+        let mut view = AbcView::new(None, BTreeMap::new()).unwrap();
+        let out = view.render(&notes).unwrap();
+        assert!(out.ends_with("\n^F =F3/2 c'/2 B,8\n"));
+    }
+
+    #[test]
+    fn test_render_notated_duration() {
+        let note: SingleNote<ETPitch, RatioDuration> = SingleNote::new(60, RatioDuration(1, 8))
+            .notated_as(RatioDuration(1, 4));
+        assert_eq!("c4\n", &note.render_default().unwrap());
+        assert_eq!(note.duration(), Duration(RatioDuration(1, 8)));
+        let value = serde_json::to_value(&note).unwrap();
+        assert_eq!(value["duration"], json!([1, 8]));
+    }
+
+    #[test]
+    fn test_render_from_templates() {
+        let notes = Notes::new(initialize_notes());
+        let mut templates = HashMap::new();
+        templates.insert("notes", "{{#each notes as |note|}}[{{view_note note}}]{{/each}}");
+        let mut view = NotesView::from_templates(&templates, BTreeMap::new()).unwrap();
+        let out = notes.render(&mut view).unwrap();
+        assert_eq!("[c2][d4][e4][f4]", &out);
+    }
+
+    #[test]
+    fn test_notes_builder() {
+        let notes = NotesBuilder::new()
+            .note(60, (1, 2))
+            .notes(&[(62, (1, 4)), (64, (1, 4))])
+            .note(65, (1, 4))
+            .build();
+        assert_eq!(notes.data, initialize_notes());
+    }
+
+    #[test]
+    fn test_render_rest_template() {
+        let rest: Rest<RatioDuration> = Rest::new((1, 4));
+        assert_eq!("r4\n", &rest.render_default().unwrap());
+    }
+
+    #[test]
+    fn test_round_trip_lilypond() {
+        let notes = Notes::from_lilypond("c4 d8 e8 f2").unwrap();
+        let mut view = View::new(None, BTreeMap::new()).unwrap();
+        let out = notes.render(&mut view).unwrap();
+        assert_eq!(" c4  d8  e8  f2 \n", &out);
+
+        let notes = Notes::from_lilypond("g4. a8 bflat").unwrap();
+        let mut view = View::new(None, BTreeMap::new()).unwrap();
+        let out = notes.render(&mut view).unwrap();
+        assert_eq!(" g4.  a8  bflat8 \n", &out);
+    }
+
+    #[test]
+    fn test_quantize_onsets() {
+        // Half a second apart at 120 BPM is a quarter note, give or take some jitter
+        let onsets = [0.0, 0.51, 0.98, 1.5, 2.02];
+        let notes = quantize(&onsets, 120., 16).unwrap();
+        assert_eq!(notes.data.len(), 4);
+        for note in notes.data.iter() {
+            assert_eq!(note.duration(), Duration(RatioDuration(1, 4)));
+            assert_eq!(note.pitch().midi, 60);
+        }
+        assert!(quantize(&onsets, 120., 0).is_err());
+        assert!(quantize(&[], 120., 16).unwrap().data.is_empty());
+    }
+
+    #[test]
+    fn test_melody_from_intervals() {
+        let midi = |notes: Notes<SingleNote<ETPitch, RatioDuration>>| {
+            notes.data.iter().map(|note| note.pitch().midi).collect::<Vec<u32>>()
+        };
+        let steps = [2, 2, 1, 2, 2, 2, 1];
+        let melody = melody_from_intervals(ETPitch::new(60), &steps, &[(1, 8); 7]).unwrap();
+        assert_eq!(midi(melody), vec![62, 64, 65, 67, 69, 71, 72]);
+
+        let scale = melody_from_intervals(ETPitch::new(60), &[0, 2, 2, 1, 2, 2, 2, 1], &[(1, 8); 8]).unwrap();
+        assert_eq!(scale.total_duration(), Duration(RatioDuration(1, 1)));
+        assert_eq!(midi(scale), vec![60, 62, 64, 65, 67, 69, 71, 72]);
+
+        assert!(melody_from_intervals(ETPitch::new(60), &steps, &[(1, 8); 6]).is_err());
+        assert!(melody_from_intervals(ETPitch::new(2), &[-3], &[(1, 4)]).is_err());
+    }
+
+    #[test]
+    fn test_lilypond_relative() {
+        let midi = |notes: Notes<SingleNote<ETPitch, RatioDuration>>| {
+            notes.data.iter().map(|note| note.pitch().midi).collect::<Vec<u32>>()
+        };
+        let scale = Notes::from_lilypond_relative(ETPitch::new(60), "c d e f g").unwrap();
+        assert_eq!(midi(scale), vec![60, 62, 64, 65, 67]);
+
+        let leaps = Notes::from_lilypond_relative(ETPitch::new(60), "c g c g' b, fis").unwrap();
+        assert_eq!(midi(leaps), vec![60, 55, 60, 67, 59, 54]);
+
+        // A fourth goes the short way, and an augmented fourth counts as a fourth by letter name
+        let fourths = Notes::from_lilypond_relative(ETPitch::new(60), "f c fis c ges").unwrap();
+        assert_eq!(midi(fourths), vec![65, 60, 66, 60, 54]);
+    }
+
+    #[test]
+    fn test_lilypond_parse_error() {
+        let err = Notes::from_lilypond("c4 d8 q8 f2").err().unwrap();
+        assert_eq!(err.token, "q8");
+        assert_eq!(err.to_string(), "Unknown pitch name: \"q8\"");
+
+        // A duration too short to hold is an error, not an overflow
+        let err = Notes::from_lilypond("c4 c1073741824.. d4").err().unwrap();
+        assert_eq!(err.to_string(), "Duration is too short to hold: \"c1073741824..\"");
+        let dots = format!("c4{}", ".".repeat(40));
+        assert!(Notes::from_lilypond(&dots).is_err());
     }
 
     #[test]
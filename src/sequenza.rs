@@ -3,171 +3,980 @@
 //! trait, which allows for generalization over various types of groupings, and the possibility for
 //! `Note` values to overflow one grouping or another.
 
-use super::{Duration, Durational};
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::ser::{SerializeStruct, Error};
+
+use std::collections::VecDeque;
+
+use super::{gcd, Duration, Durational};
 
 /// Primary trait of a given hierarchical level. 
-pub trait Grouping<D> 
+pub trait Grouping<D>: GroupingClone<D> 
 where D: Durational
 {
     fn duration(&self) -> Duration<D>;
 
-    fn next(&mut self) -> Option<Box<Grouping<D>>> { None }
+    fn next(&mut self) -> Option<Box<dyn Grouping<D>>> { None }
     fn is_empty(&self) -> bool { true }
 
     fn start_annotation(&self) -> &str { "" } 
     fn end_annotation(&self) -> &str { "" }
+
+    /// The `start_annotation()` for a `Grouping` beginning in the given measure, counting from 1.
+    /// This is where a `Measure` writes its own number.
+    fn numbered_start_annotation(&self, _measure: u32) -> String { self.start_annotation().to_string() }
+
+    /// Ratio of written time to actual time within the `Grouping`, such as (3, 2) for a triplet.
+    fn time_scale(&self) -> (u32, u32) { (1, 1) }
+
+    /// Returns the `Grouping` as a `SerializedGrouping`, if its type can be serialized.
+    fn to_serialized(&self) -> Option<SerializedGrouping<D>> { None }
+
+    /// Whether the `Grouping` is a bar of music, as opposed to a beat or region within one.
+    fn is_measure(&self) -> bool { false }
+
+    /// Whether the notes within the `Grouping` are joined by a beam, which is written by hand
+    /// (`c8[ c8]`) and closed before any tie that leaves the `Grouping`.
+    fn is_beamed(&self) -> bool { false }
+}
+
+/// Allows a boxed `Grouping` to be cloned. This is implemented for every `Grouping` that is
+/// `Clone` and `'static`, and should not need to be implemented by hand. Since it is a
+/// supertrait of `Grouping`, a type must be `Clone` to be used as a `Grouping`.
+pub trait GroupingClone<D> 
+where D: Durational
+{
+    fn box_clone(&self) -> Box<dyn Grouping<D>>;
+}
+
+impl<D, T> GroupingClone<D> for T 
+where D: 'static + Durational,
+      T: 'static + Grouping<D> + Clone
+{
+    fn box_clone(&self) -> Box<dyn Grouping<D>> {
+        Box::new(self.clone())
+    }
+}
+
+impl<D> Clone for Box<dyn Grouping<D>> 
+where D: 'static + Durational
+{
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+/// Tagged representation of the `Grouping` types that can be serialized, used to store the
+/// contents of a `Measure`.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum SerializedGrouping<D> 
+where D: 'static + Durational
+{
+    Beat(Beat<D>),
+    Measure(Measure<D>)
+}
+
+impl<D> From<SerializedGrouping<D>> for Box<dyn Grouping<D>> 
+where D: 'static + Durational
+{
+    fn from(grouping: SerializedGrouping<D>) -> Self {
+        match grouping {
+            SerializedGrouping::Beat(beat) => Box::new(beat),
+            SerializedGrouping::Measure(measure) => Box::new(measure)
+        }
+    }
 }
 
 /// The simplest form of `Grouping`, which has a particular duration and does not allow a given
 /// `Note` to overflow its bounds.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Beat<D> 
 where D: Durational
 {
-    duration: Duration<D>
+    duration: Duration<D>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mark: Option<BoundaryMark>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    beamed: bool
 }
 
 /// A `Grouping` which contains other `Grouping`s.
+#[derive(Clone)]
 pub struct Measure<D> 
-where D: Durational
+where D: 'static + Durational
+{
+    duration: Duration<D>,
+    contents: Vec<Box<dyn Grouping<D>>>,
+    barline: BarlineType,
+    mark: Option<BoundaryMark>,
+    custom_start: Option<String>,
+    custom_end: Option<String>,
+    end: String
+}
+
+/// The kind of barline that closes a `Measure`.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum BarlineType {
+    #[default]
+    Single,
+    Double,
+    Final,
+    RepeatStart,
+    RepeatEnd
+}
+
+/// A mark written where a `Beat` or `Measure` ends, rather than on any one note.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BoundaryMark {
+    /// Holds the end of the grouping (`\fermata`).
+    Fermata,
+    /// A breath mark (`\breathe`).
+    Breathe,
+    /// A complete break in the music (`\caesura`).
+    Caesura
+}
+
+/// A `Grouping` which surrounds all of the `Grouping`s it contains with some fixed text, such as a
+/// Lilypond property override and its revert, or the braces of a block.
+#[derive(Clone)]
+pub struct Region<D> 
+where D: 'static + Durational
+{
+    duration: Duration<D>,
+    contents: Vec<Box<dyn Grouping<D>>>,
+    start: String,
+    end: String
+}
+
+/// A `Grouping` which repeats its contents (`\repeat volta`), optionally followed by a set of
+/// alternative endings. The endings are stored after the body in `contents`, so that they are
+/// visited in order by the `GroupingController`.
+#[derive(Clone)]
+pub struct Repeat<D> 
+where D: 'static + Durational
+{
+    duration: Duration<D>,
+    contents: Vec<Box<dyn Grouping<D>>>,
+    times: u32,
+    start: String,
+    end: &'static str
+}
+
+/// A `Grouping` whose contents are written with a different note value than they actually take
+/// up (`\tuplet 3/2 { ... }`). The contents are given in actual time, so that their durations
+/// add up to the duration of the `Tuplet` itself, and any `Tuplet`s may be nested within each
+/// other.
+#[derive(Clone)]
+pub struct Tuplet<D> 
+where D: 'static + Durational
 {
     duration: Duration<D>,
-    contents: Vec<Box<Grouping<D>>>
+    contents: Vec<Box<dyn Grouping<D>>>,
+    ratio: (u32, u32),
+    bracket: bool,
+    number: TupletNumber,
+    start: String
+}
+
+/// How the number over a `Tuplet` is printed.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TupletNumber {
+    /// Just the number of written notes, such as `3` for a triplet.
+    #[default]
+    Denominator,
+    /// The full ratio, such as `3:2`.
+    Fraction,
+    /// No number at all.
+    Hidden
 }
 
-pub struct ControlledGrouping<D> 
+/// A `Grouping` of some common kind, stored without boxing. Formatting against `StaticGrouping`s
+/// avoids an allocation and a dynamic call for every `Grouping` the `GroupingController` visits.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StaticGrouping<D> 
+where D: Durational
+{
+    Beat(Beat<D>),
+    Measure {
+        duration: Duration<D>,
+        contents: VecDeque<StaticGrouping<D>>,
+        barline: BarlineType
+    }
+}
+
+/// Anything that the `GroupingController` can hold on its stack. This is implemented for boxed
+/// `Grouping` trait objects and for `StaticGrouping`, and sub-groupings come back as the same type
+/// as the `Grouping` containing them.
+pub trait GroupingNode<D>: Sized 
+where D: Durational
+{
+    fn duration(&self) -> Duration<D>;
+    fn next(&mut self) -> Option<Self>;
+    fn is_empty(&self) -> bool;
+    fn start_annotation(&self) -> &str;
+    fn end_annotation(&self) -> &str;
+    fn numbered_start_annotation(&self, measure: u32) -> String;
+    fn time_scale(&self) -> (u32, u32);
+    fn is_measure(&self) -> bool;
+    fn is_beamed(&self) -> bool;
+}
+
+#[derive(Clone)]
+pub struct ControlledGrouping<D, G = Box<dyn Grouping<D>>> 
 where D: Durational
 {
     pub left: Duration<D>,
-    pub grouping: Box<Grouping<D>>
+    pub grouping: G
 }
 
 /// GroupingController holds a stack of groupings, and an iterator
-pub struct GroupingController<D> 
+pub struct GroupingController<D, G = Box<dyn Grouping<D>>> 
+where D: Durational
+{
+    pub stack: Vec<ControlledGrouping<D, G>>,
+    pub queue: Box<dyn Iterator<Item=G>>,
+    measures: u32,
+    style: OutputStyle,
+    bar_checks: bool,
+    tie: String,
+    line_breaks: Option<u32>,
+    /// The groupings of a controller made with `from_vec()`, read by index in place of `queue`.
+    buffer: Option<Box<dyn Fn(usize) -> Option<G>>>,
+    /// Index of the next grouping to read from `buffer`.
+    cursor: usize
+}
+
+/// How the text formatted against a `GroupingController` is laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputStyle {
+    /// Every annotation is written as it is, with a numbered comment and a new line at the start
+    /// of each measure.
+    #[default]
+    Pretty,
+    /// Measure comments are left out, and every run of whitespace becomes a single space, with
+    /// none at either end.
+    Compact
+}
+
+/// The position of a `GroupingController`, saved with `save()` so that it can be returned to with
+/// `restore()`.
+#[derive(Clone)]
+pub struct Checkpoint<D, G = Box<dyn Grouping<D>>> 
 where D: Durational
 {
-    pub stack: Vec<ControlledGrouping<D>>,
-    pub queue: Box<Iterator<Item=Box<Grouping<D>>>>
+    stack: Vec<ControlledGrouping<D, G>>,
+    queue: Vec<G>,
+    cursor: usize,
+    measures: u32
 }
 
 impl<D> Beat<D> 
 where D: Durational
 {
-    pub fn new_ratio(a: u32, b: u32) -> Self {
+    /// Creates a `Beat` lasting `duration`, such as one worked out from other durations.
+    pub fn new(duration: Duration<D>) -> Self {
         Beat {
-            duration: Duration(D::new(a, b))
+            duration,
+            mark: None,
+            beamed: false
         }
     }
+
+    pub fn new_ratio(a: u32, b: u32) -> Self {
+        Beat::new(Duration(D::new(a, b)))
+    }
+
+    /// Joins the notes within the `Beat` with a beam.
+    pub fn beamed(mut self) -> Self {
+        self.beamed = true;
+        self
+    }
+
+    /// Writes a mark, such as a breath mark, at the end of the `Beat`.
+    pub fn with_mark(mut self, mark: BoundaryMark) -> Self {
+        self.mark = Some(mark);
+        self
+    }
 }
 
 impl<D> Grouping<D> for Beat<D> 
-where D: Durational
+where D: 'static + Durational
 {
     fn duration(&self) -> Duration<D> {
         self.duration
     }
+
+    fn end_annotation(&self) -> &str {
+        self.mark.map(|mark| mark.as_lilypond()).unwrap_or("")
+    }
+
+    fn is_beamed(&self) -> bool { self.beamed }
+
+    fn to_serialized(&self) -> Option<SerializedGrouping<D>> {
+        Some(SerializedGrouping::Beat(self.clone()))
+    }
 }
 
 impl<D> Measure<D> 
-where D: Durational
+where D: 'static + Durational
 {
-    pub fn from_contents(contents: Vec<Box<Grouping<D>>>) -> Self {
-        let total_duration = contents.iter().fold(Duration::<D>::new(0, 1), |acc, d| {
-            d.duration() + acc
-        });
-
+    pub fn from_contents(contents: Vec<Box<dyn Grouping<D>>>) -> Self {
         Measure {
-            duration: total_duration,
-            contents: contents
+            duration: total_duration(&contents),
+            contents,
+            barline: BarlineType::Single,
+            mark: None,
+            custom_start: None,
+            custom_end: None,
+            end: BarlineType::Single.as_lilypond().to_string()
+        }
+    }
+
+    /// Creates a `Measure` as in `from_contents`, but fails unless the contents add up to
+    /// `expected`, such as the length of a bar in the intended time signature.
+    pub fn from_contents_checked(contents: Vec<Box<dyn Grouping<D>>>, expected: Duration<D>) -> Result<Self, &'static str> {
+        let measure = Measure::from_contents(contents);
+        if measure.duration.reduced().as_ratio() != expected.reduced().as_ratio() {
+            return Err("Contents of the measure do not add up to the expected duration");
+        }
+        Ok(measure)
+    }
+
+    /// Sets the barline printed at the end of the `Measure`.
+    pub fn with_barline(mut self, barline: BarlineType) -> Self {
+        self.barline = barline;
+        self.end = self.end_text();
+        self
+    }
+
+    /// Writes a mark, such as a fermata, just before the barline at the end of the `Measure`.
+    pub fn with_mark(mut self, mark: BoundaryMark) -> Self {
+        self.mark = Some(mark);
+        self.end = self.end_text();
+        self
+    }
+
+    /// Takes `by` away from the end of the `Measure`, dropping its last `Grouping`s. Where only
+    /// part of a `Grouping` is taken away, what is left of it becomes a plain `Beat`. The barline
+    /// and any other annotations are kept.
+    pub fn shortened(mut self, by: Duration<D>) -> Result<Self, &'static str> {
+        if by >= self.duration {
+            return Err("Cannot shorten a measure by its whole duration");
+        }
+        let mut left = by;
+        while left.as_float() > 0.0 {
+            let last = self.contents.pop().ok_or("Measure has no contents to shorten")?;
+            let duration = last.duration();
+            if duration > left {
+                self.contents.push(Box::new(Beat::new(duration - left)));
+                break;
+            }
+            left = left - duration;
         }
+        self.duration = total_duration(&self.contents);
+        Ok(self)
+    }
+
+    /// Writes `start` at the start of the `Measure` in place of the comment giving its number.
+    /// An empty string leaves the comment out altogether.
+    pub fn with_start_annotation(mut self, start: &str) -> Self {
+        self.custom_start = Some(start.to_string());
+        self
+    }
+
+    /// Writes `end` at the end of the `Measure` in place of its barline. Any mark is still
+    /// written before it.
+    pub fn with_end_annotation(mut self, end: &str) -> Self {
+        self.custom_end = Some(end.to_string());
+        self.end = self.end_text();
+        self
+    }
+
+    fn end_text(&self) -> String {
+        let mark = self.mark.map(|mark| mark.as_lilypond()).unwrap_or("");
+        let barline = self.custom_end.as_deref().unwrap_or(self.barline.as_lilypond());
+        format!("{}{}", mark, barline)
+    }
+
+    /// Creates a `Measure` of `Beat`s for the time signature `numerator/denominator`. Compound
+    /// meters (6/8, 9/8, 12/16 and so on) are grouped into dotted beats of three notes each, so
+    /// that notes are split at the dotted beats rather than at every eighth. All other meters get
+    /// one `Beat` for each unit of the denominator.
+    pub fn from_time_signature(numerator: u32, denominator: u32) -> Self {
+        let is_compound = numerator > 3 && numerator.is_multiple_of(3) && denominator >= 8;
+        let beats: Vec<Box<dyn Grouping<D>>> = if is_compound {
+            (0..numerator / 3)
+                .map(|_| Box::new(Beat::new_ratio(3, denominator)) as Box<dyn Grouping<D>>)
+                .collect()
+        } else {
+            (0..numerator)
+                .map(|_| Box::new(Beat::new_ratio(1, denominator)) as Box<dyn Grouping<D>>)
+                .collect()
+        };
+        Measure::from_contents(beats)
     }
 }
 
 impl<D> Grouping<D> for Measure<D> 
+where D: 'static + Durational
+{
+    fn duration(&self) -> Duration<D> {
+        self.duration
+    }
+
+    fn next(&mut self) -> Option<Box<dyn Grouping<D>>> { 
+        if self.contents.is_empty() {
+            None
+        } else {
+            Some(self.contents.remove(0))
+        }
+    }
+
+    fn is_empty(&self) -> bool { 
+        self.contents.is_empty()
+    }
+
+    fn start_annotation(&self) -> &str { self.custom_start.as_deref().unwrap_or(" %m. \n ") }
+    fn end_annotation(&self) -> &str { &self.end }
+    fn numbered_start_annotation(&self, measure: u32) -> String { 
+        match self.custom_start {
+            Some(ref start) => start.clone(),
+            None => measure_comment(measure)
+        }
+    }
+
+    fn to_serialized(&self) -> Option<SerializedGrouping<D>> {
+        Some(SerializedGrouping::Measure(self.clone()))
+    }
+
+    fn is_measure(&self) -> bool { true }
+}
+
+impl<D> Serialize for Measure<D> 
+where D: Durational + Serialize
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> 
+        where S: Serializer
+    {
+        let contents = self.contents.iter()
+            .map(|grouping| grouping.to_serialized())
+            .collect::<Option<Vec<SerializedGrouping<D>>>>()
+            .ok_or_else(|| S::Error::custom("Measure contains a Grouping that cannot be serialized"))?;
+        let len = 2 + self.mark.iter().count() + self.custom_start.iter().count() + self.custom_end.iter().count();
+        let mut state = serializer.serialize_struct("Measure", len)?;
+        state.serialize_field("contents", &contents)?;
+        state.serialize_field("barline", &self.barline)?;
+        match self.mark {
+            Some(mark) => state.serialize_field("mark", &mark)?,
+            None => state.skip_field("mark")?
+        }
+        match self.custom_start {
+            Some(ref start) => state.serialize_field("start", start)?,
+            None => state.skip_field("start")?
+        }
+        match self.custom_end {
+            Some(ref end) => state.serialize_field("end", end)?,
+            None => state.skip_field("end")?
+        }
+        state.end()
+    }
+}
+
+/// The serialized fields of a `Measure`, whose duration is recalculated from its contents.
+#[derive(Deserialize)]
+#[serde(rename = "Measure")]
+struct MeasureFields<D> 
+where D: 'static + Durational
+{
+    contents: Vec<SerializedGrouping<D>>,
+    barline: BarlineType,
+    #[serde(default)]
+    mark: Option<BoundaryMark>,
+    #[serde(default)]
+    start: Option<String>,
+    #[serde(default)]
+    end: Option<String>
+}
+
+impl<'de, D> Deserialize<'de> for Measure<D> 
+where D: Durational + Deserialize<'de>
+{
+    fn deserialize<De>(deserializer: De) -> Result<Self, De::Error> 
+        where De: Deserializer<'de>
+    {
+        let fields = MeasureFields::<D>::deserialize(deserializer)?;
+        let contents = fields.contents.into_iter().map(Box::from).collect();
+        let mut measure = Measure::from_contents(contents).with_barline(fields.barline);
+        measure.mark = fields.mark;
+        measure.custom_start = fields.start;
+        measure.custom_end = fields.end;
+        measure.end = measure.end_text();
+        Ok(measure)
+    }
+}
+
+impl BoundaryMark {
+    pub fn as_lilypond(&self) -> &'static str {
+        match *self {
+            BoundaryMark::Fermata => " \\fermata",
+            BoundaryMark::Breathe => " \\breathe",
+            BoundaryMark::Caesura => " \\caesura"
+        }
+    }
+}
+
+impl BarlineType {
+    pub fn as_lilypond(&self) -> &'static str {
+        match *self {
+            BarlineType::Single => " |\n ",
+            BarlineType::Double => " \\bar \"||\"\n ",
+            BarlineType::Final => " \\bar \"|.\"\n ",
+            BarlineType::RepeatStart => " \\bar \".|:\"\n ",
+            BarlineType::RepeatEnd => " \\bar \":|.\"\n "
+        }
+    }
+}
+
+
+impl<D> Region<D> 
 where D: Durational
+{
+    pub fn new(start: &str, end: &str, contents: Vec<Box<dyn Grouping<D>>>) -> Self {
+        Region {
+            duration: total_duration(&contents),
+            contents,
+            start: start.to_string(),
+            end: end.to_string()
+        }
+    }
+
+    /// Overrides `property` (for example, `Stem.length`) with the Scheme `value` for the duration
+    /// of the `Region`.
+    pub fn override_property(property: &str, value: &str, contents: Vec<Box<dyn Grouping<D>>>) -> Self {
+        Region::new(&format!("\\override {} = #{} ", property, value),
+                    &format!(" \\revert {} ", property),
+                    contents)
+    }
+
+    /// Overrides the length of every stem in the `Region`, measured in staff spaces.
+    pub fn stem_length(length: f64, contents: Vec<Box<dyn Grouping<D>>>) -> Self {
+        Region::override_property("Stem.length", &length.to_string(), contents)
+    }
+}
+
+impl<D> Grouping<D> for Region<D> 
+where D: 'static + Durational
+{
+    fn duration(&self) -> Duration<D> {
+        self.duration
+    }
+
+    fn next(&mut self) -> Option<Box<dyn Grouping<D>>> { 
+        if self.contents.is_empty() {
+            None
+        } else {
+            Some(self.contents.remove(0))
+        }
+    }
+
+    fn is_empty(&self) -> bool { 
+        self.contents.is_empty()
+    }
+
+    fn start_annotation(&self) -> &str { &self.start }
+    fn end_annotation(&self) -> &str { &self.end }
+}
+
+impl<D> Repeat<D> 
+where D: 'static + Durational
+{
+    /// Repeats `contents` the given number of times.
+    pub fn volta(times: u32, contents: Vec<Box<dyn Grouping<D>>>) -> Self {
+        Repeat {
+            duration: total_duration(&contents),
+            contents,
+            times,
+            start: format!("\\repeat volta {} {{ ", times),
+            end: " } "
+        }
+    }
+
+    /// Repeats `measures` after a pickup (`\\partial`) of the given duration, such as the upbeat
+    /// of a folk tune. The pickup is taken away from the last of the `measures`, since coming
+    /// back round the repeat it completes that measure, so that every pass adds up to whole
+    /// measures. The pickup itself is not counted as a measure.
+    ///
+    /// Lilypond writes the body only once, so at the end of the shortened measure it is partway
+    /// through a measure, and a bar check there would fail. The shortened measure instead ends by
+    /// moving Lilypond back to the start of a measure (`\set Timing.measurePosition`), so that its
+    /// bar check passes and the music after the repeat stays in step.
+    pub fn with_pickup(times: u32, pickup: Duration<D>, mut measures: Vec<Measure<D>>) -> Result<Self, &'static str> {
+        let last = measures.pop().ok_or("Repeat with a pickup needs at least one measure")?;
+        measures.push(last.shortened(pickup)?
+            .with_end_annotation(" \\set Timing.measurePosition = #(ly:make-moment 0) |\n "));
+        let start = format!("\\partial {} ", pickup.as_lilypond());
+        let pickup: Box<dyn Grouping<D>> = Box::new(Region::new(&start, " |\n ", vec![Box::new(Beat::new(pickup))]));
+        let contents = Some(pickup).into_iter()
+            .chain(measures.into_iter().map(|measure| Box::new(measure) as Box<dyn Grouping<D>>))
+            .collect();
+        Ok(Repeat::volta(times, contents))
+    }
+
+    /// Adds alternative endings, each of which is played on a different pass through the repeat.
+    /// The repeat count is raised to the number of endings if necessary.
+    ///
+    /// A note running past the end of the body is tied into the first ending, which is where its
+    /// remainder is written, and Lilypond draws the tie across the repeat as it should. The notes
+    /// of a later ending follow on from the first ending instead, so when one of them should begin
+    /// with the same held note, that note is marked with a `\repeatTie` annotation.
+    pub fn with_alternatives(mut self, endings: Vec<Box<dyn Grouping<D>>>) -> Self {
+        if endings.len() as u32 > self.times {
+            self.times = endings.len() as u32;
+            self.start = format!("\\repeat volta {} {{ ", self.times);
+        }
+        let endings: Vec<Box<dyn Grouping<D>>> = endings.into_iter()
+            .map(|ending| Box::new(Region::new("{ ", " } ", vec![ending])) as Box<dyn Grouping<D>>)
+            .collect();
+        // The alternative block closes the body of the repeat before opening
+        let alternatives = Region::new("} \\alternative { ", " } ", endings);
+        self.duration = self.duration + alternatives.duration();
+        self.contents.push(Box::new(alternatives));
+        self.end = "";
+        self
+    }
+}
+
+impl<D> Grouping<D> for Repeat<D> 
+where D: 'static + Durational
 {
     fn duration(&self) -> Duration<D> {
         self.duration
     }
 
-    fn next(&mut self) -> Option<Box<Grouping<D>>> { 
-        self.contents.pop()
+    fn next(&mut self) -> Option<Box<dyn Grouping<D>>> { 
+        if self.contents.is_empty() {
+            None
+        } else {
+            Some(self.contents.remove(0))
+        }
     }
 
     fn is_empty(&self) -> bool { 
         self.contents.is_empty()
     }
 
-    fn start_annotation(&self) -> &str { " %m. \n " }
-    fn end_annotation(&self) -> &str { " |\n " }
+    fn start_annotation(&self) -> &str { &self.start }
+    fn end_annotation(&self) -> &str { self.end }
 }
 
-impl<D> Into<ControlledGrouping<D>> for Box<Grouping<D>> 
+impl<D> Tuplet<D> 
 where D: Durational
 {
-    fn into(self) -> ControlledGrouping<D> {
-        ControlledGrouping {
-            left: self.duration(),
-            grouping: self
+    /// Creates a `Tuplet` where `ratio.0` written notes take the time of `ratio.1`.
+    pub fn new(ratio: (u32, u32), contents: Vec<Box<dyn Grouping<D>>>) -> Self {
+        let mut tuplet = Tuplet {
+            duration: total_duration(&contents),
+            contents,
+            ratio,
+            bracket: true,
+            number: TupletNumber::Denominator,
+            start: String::new()
+        };
+        tuplet.start = tuplet.start_text();
+        tuplet
+    }
+
+    /// Leaves out the bracket over the `Tuplet`.
+    pub fn without_bracket(mut self) -> Self {
+        self.bracket = false;
+        self.start = self.start_text();
+        self
+    }
+
+    /// Sets how the number over the `Tuplet` is printed.
+    pub fn with_number(mut self, number: TupletNumber) -> Self {
+        self.number = number;
+        self.start = self.start_text();
+        self
+    }
+
+    /// The `\\tuplet` command, preceded by an override for each setting that differs from
+    /// Lilypond's own default. Each override applies only to this `Tuplet`.
+    fn start_text(&self) -> String {
+        let bracket = if self.bracket { "" } else { "\\once \\override TupletBracket.bracket-visibility = ##f " };
+        let number = match self.number {
+            TupletNumber::Denominator => "",
+            TupletNumber::Fraction => "\\once \\override TupletNumber.text = #tuplet-number::calc-fraction-text ",
+            TupletNumber::Hidden => "\\once \\override TupletNumber.stencil = ##f "
+        };
+        format!("{}{}\\tuplet {}/{} {{ ", bracket, number, self.ratio.0, self.ratio.1)
+    }
+}
+
+impl<D> Grouping<D> for Tuplet<D> 
+where D: 'static + Durational
+{
+    fn duration(&self) -> Duration<D> {
+        self.duration
+    }
+
+    fn next(&mut self) -> Option<Box<dyn Grouping<D>>> { 
+        if self.contents.is_empty() {
+            None
+        } else {
+            Some(self.contents.remove(0))
+        }
+    }
+
+    fn is_empty(&self) -> bool { 
+        self.contents.is_empty()
+    }
+
+    fn start_annotation(&self) -> &str { &self.start }
+    fn end_annotation(&self) -> &str { " } " }
+
+    fn time_scale(&self) -> (u32, u32) { self.ratio }
+}
+
+impl<D> StaticGrouping<D> 
+where D: Durational
+{
+    pub fn beat(a: u32, b: u32) -> Self {
+        StaticGrouping::Beat(Beat::new_ratio(a, b))
+    }
+
+    pub fn measure(contents: Vec<StaticGrouping<D>>) -> Self {
+        StaticGrouping::Measure {
+            duration: contents.iter().fold(Duration::<D>::new(0, 1), |acc, d| d.duration() + acc),
+            contents: contents.into(),
+            barline: BarlineType::Single
+        }
+    }
+
+    /// Sets the barline printed at the end of a `Measure`. Has no effect on a `Beat`.
+    pub fn with_barline(mut self, new_barline: BarlineType) -> Self {
+        if let StaticGrouping::Measure { ref mut barline, .. } = self {
+            *barline = new_barline;
+        }
+        self
+    }
+}
+
+impl<D> GroupingNode<D> for StaticGrouping<D> 
+where D: Durational
+{
+    fn duration(&self) -> Duration<D> {
+        match *self {
+            StaticGrouping::Beat(ref beat) => beat.duration,
+            StaticGrouping::Measure { duration, .. } => duration
+        }
+    }
+
+    fn next(&mut self) -> Option<Self> {
+        match *self {
+            StaticGrouping::Beat(_) => None,
+            StaticGrouping::Measure { ref mut contents, .. } => contents.pop_front()
         }
     }
+
+    fn is_empty(&self) -> bool {
+        match *self {
+            StaticGrouping::Beat(_) => true,
+            StaticGrouping::Measure { ref contents, .. } => contents.is_empty()
+        }
+    }
+
+    fn start_annotation(&self) -> &str {
+        match *self {
+            StaticGrouping::Beat(_) => "",
+            StaticGrouping::Measure { .. } => " %m. \n "
+        }
+    }
+
+    fn end_annotation(&self) -> &str {
+        match *self {
+            StaticGrouping::Beat(ref beat) => beat.mark.map(|mark| mark.as_lilypond()).unwrap_or(""),
+            StaticGrouping::Measure { barline, .. } => barline.as_lilypond()
+        }
+    }
+
+    fn numbered_start_annotation(&self, measure: u32) -> String {
+        match *self {
+            StaticGrouping::Beat(_) => String::new(),
+            StaticGrouping::Measure { .. } => measure_comment(measure)
+        }
+    }
+
+    fn time_scale(&self) -> (u32, u32) { (1, 1) }
+
+    fn is_measure(&self) -> bool {
+        match *self {
+            StaticGrouping::Beat(_) => false,
+            StaticGrouping::Measure { .. } => true
+        }
+    }
+
+    fn is_beamed(&self) -> bool {
+        match *self {
+            StaticGrouping::Beat(ref beat) => beat.beamed,
+            StaticGrouping::Measure { .. } => false
+        }
+    }
+}
+
+impl<D> GroupingNode<D> for Box<dyn Grouping<D>> 
+where D: Durational
+{
+    fn duration(&self) -> Duration<D> { Grouping::duration(&**self) }
+    fn next(&mut self) -> Option<Self> { Grouping::next(&mut **self) }
+    fn is_empty(&self) -> bool { Grouping::is_empty(&**self) }
+    fn start_annotation(&self) -> &str { Grouping::start_annotation(&**self) }
+    fn end_annotation(&self) -> &str { Grouping::end_annotation(&**self) }
+    fn numbered_start_annotation(&self, measure: u32) -> String { Grouping::numbered_start_annotation(&**self, measure) }
+    fn time_scale(&self) -> (u32, u32) { Grouping::time_scale(&**self) }
+    fn is_measure(&self) -> bool { Grouping::is_measure(&**self) }
+    fn is_beamed(&self) -> bool { Grouping::is_beamed(&**self) }
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// Comment written at the start of each `Measure`, giving its number, unless the `Measure` has a
+/// start annotation of its own.
+pub fn measure_comment(measure: u32) -> String {
+    format!(" %m. {}\n ", measure)
+}
+
+/// `Grouping`s without any duration can't hold a note, so the `GroupingController` passes over
+/// them (along with their annotations) rather than stopping on them.
+fn has_duration<D, G>(grouping: &G) -> bool 
+where D: Durational,
+      G: GroupingNode<D>
+{
+    grouping.duration().as_float() > 0.0
 }
 
-impl<D> ControlledGrouping<D> 
+fn total_duration<D>(contents: &[Box<dyn Grouping<D>>]) -> Duration<D> 
 where D: Durational
+{
+    contents.iter().fold(Duration::<D>::new(0, 1), |acc, d| {
+        d.duration() + acc
+    })
+}
+
+impl<D, G> From<G> for ControlledGrouping<D, G> 
+where D: Durational,
+      G: GroupingNode<D>
+{
+    fn from(grouping: G) -> Self {
+        ControlledGrouping {
+            left: grouping.duration(),
+            grouping
+        }
+    }
+}
+
+impl<D, G> ControlledGrouping<D, G> 
+where D: Durational,
+      G: GroupingNode<D>
 {
     pub fn is_start_of_grouping(&self) -> bool {
         self.grouping.duration().as_ratio() == self.left.as_ratio()
     }
 }
 
-impl<D> GroupingController<D> 
-where D: Durational
+impl<D, G> GroupingController<D, G> 
+where D: Durational,
+      G: GroupingNode<D>
 {
-    pub fn new(mut groupings: Box<Iterator<Item=Box<Grouping<D>>>>) -> Result<Self, &'static str> {
-        let mut current: Vec<ControlledGrouping<D>> = vec![];
-        let current_grouping = groupings.next()
-            .ok_or("Passed empty groupings iterator")?;
-
-        let count_left = current_grouping.duration();
-        current.push(
-            ControlledGrouping {
-                left: count_left,
-                grouping: current_grouping
-            });
-
-        // If the top-level grouping has a sub-grouping...
-        if let Some(sub_grouping) = current.last_mut().unwrap().grouping.next() {
-            let count_left = sub_grouping.duration();
-            current.push(
-                ControlledGrouping {
-                    left: count_left,
-                    grouping: sub_grouping
-                });
-        }
-
-        Ok(GroupingController {
-            stack: current,
-            queue: groupings
-        })
+    pub fn new(groupings: Box<dyn Iterator<Item=G>>) -> Result<Self, &'static str> {
+        GroupingController::from_source(groupings, None)
+    }
+
+    /// Creates a controller over a `Vec` of groupings, which it takes ownership of. The groupings
+    /// are kept in the controller and read by index, a copy at a time, so that the controller can
+    /// go back to any point in them: `save()` only has to note the index of the next grouping,
+    /// and `reset()` starts over from the first. The `queue` of such a controller is left empty.
+    pub fn from_vec(groupings: Vec<G>) -> Result<Self, &'static str> 
+    where G: Clone + 'static
+    {
+        let buffer = move |index: usize| groupings.get(index).cloned();
+        GroupingController::from_source(Box::new(::std::iter::empty()), Some(Box::new(buffer)))
+    }
+
+    fn from_source(queue: Box<dyn Iterator<Item=G>>, buffer: Option<Box<dyn Fn(usize) -> Option<G>>>) -> Result<Self, &'static str> {
+        let mut controller = GroupingController {
+            stack: vec![],
+            queue,
+            measures: 0,
+            style: OutputStyle::Pretty,
+            bar_checks: false,
+            tie: " ~ ".to_string(),
+            line_breaks: None,
+            buffer,
+            cursor: 0
+        };
+        controller.start()?;
+        Ok(controller)
+    }
+
+    /// Pushes the first grouping with any duration onto the stack, along with its sub-groupings.
+    fn start(&mut self) -> Result<(), &'static str> {
+        let current_grouping = self.next_queued().ok_or("Passed empty groupings iterator")?;
+        self.push(current_grouping);
+        // If the top-level grouping has sub-groupings...
+        self.descend()
+    }
+
+    /// Goes back to the first of the groupings, as though the controller had just been created.
+    /// Only a controller made with `from_vec()` keeps its groupings around to go back to.
+    pub fn reset(&mut self) -> Result<(), &'static str> {
+        if self.buffer.is_none() {
+            return Err("Only a controller made with from_vec can be reset");
+        }
+        self.stack.clear();
+        self.measures = 0;
+        self.cursor = 0;
+        self.start()
+    }
+
+    /// The next grouping with any duration, from the buffer if there is one and from the queue
+    /// otherwise.
+    fn next_queued(&mut self) -> Option<G> {
+        match self.buffer {
+            Some(ref buffer) => {
+                while let Some(grouping) = buffer(self.cursor) {
+                    self.cursor += 1;
+                    if has_duration(&grouping) {
+                        return Some(grouping);
+                    }
+                }
+                None
+            }
+            None => self.queue.by_ref().find(has_duration)
+        }
     }
 
     /// Consumes some amount of time from the controller, and returns a `Vec` of exhausted
     /// `Grouping`s. The calling `View` calls `end_annotation()` on each of these.
-    pub fn consume_time(&mut self, mut time: Duration<D>) -> Result<Vec<Box<Grouping<D>>>, &'static str> {
-        let mut out: Vec<Box<Grouping<D>>> = Vec::new();
+    pub fn consume_time(&mut self, mut time: Duration<D>) -> Result<Vec<G>, &'static str> {
+        let mut out: Vec<G> = Vec::new();
 
         while time.as_float() > 0.0 {
-            if self.current()?.left < time {
-                time = time - self.current()?.left;
-                self.deplete_time(time);
+            let left = self.current()?.left;
+            if left < time {
+                // Only the time left in the current grouping is consumed from it; the rest
+                // carries over into the groupings that follow.
+                self.deplete_time(left);
+                time = time - left;
                 out.extend(self.advance_grouping()?);
             } else if self.current()?.left > time {
                 self.deplete_time(time);
-                time = time - time;
+                time = Duration::new(0, 1);
             } else if self.current()?.left == time {
                 self.deplete_time(time);
-                time = time - time;
+                time = Duration::new(0, 1);
                 out.extend(self.advance_grouping()?);
             }
         }
@@ -175,11 +984,94 @@ where D: Durational
         Ok(out)
     }
 
-    pub fn current(&self) -> Result<&ControlledGrouping<D>, &'static str> {
+    /// The `Grouping`s beginning at the current position, from the outermost in. After
+    /// `consume_time()` these are the groupings it has just entered, whose `start_annotation()`
+    /// belongs before the next note, just as the groupings it returns are the ones whose
+    /// `end_annotation()` belongs after the last.
+    pub fn starting<'a>(&'a self) -> impl Iterator<Item=&'a G> + 'a {
+        self.stack.iter()
+            .filter(|controlled_grouping| controlled_grouping.is_start_of_grouping())
+            .map(|controlled_grouping| &controlled_grouping.grouping)
+    }
+
+    /// Ratio of written time to actual time at the current position, combining the
+    /// `time_scale()` of every `Grouping` on the stack.
+    pub fn time_scale(&self) -> (u32, u32) {
+        self.stack.iter().fold((1, 1), |acc, controlled_grouping| {
+            let scale = controlled_grouping.grouping.time_scale();
+            let (num, den) = (acc.0 * scale.0, acc.1 * scale.1);
+            let least = gcd(num, den);
+            (num / least, den / least)
+        })
+    }
+
+    /// Time left in the outermost `Measure` on the stack. When no `Measure` is on the stack, the
+    /// time left in the outermost `Grouping` is returned instead, and nothing is left once the
+    /// groupings have run out.
+    pub fn measure_remaining(&self) -> Duration<D> {
+        self.stack.iter()
+            .find(|controlled_grouping| controlled_grouping.grouping.is_measure())
+            .or_else(|| self.stack.first())
+            .map(|controlled_grouping| controlled_grouping.left)
+            .unwrap_or_else(|| Duration::new(0, 1))
+    }
+
+    /// Sets how text formatted against the controller is laid out.
+    pub fn with_style(mut self, style: OutputStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn style(&self) -> OutputStyle {
+        self.style
+    }
+
+    /// Writes a Lilypond bar check (`|`) at the end of every `Measure`, even where the barline
+    /// printed there is something else, so that Lilypond reports any note that doesn't fill its
+    /// measure.
+    pub fn with_bar_checks(mut self, bar_checks: bool) -> Self {
+        self.bar_checks = bar_checks;
+        self
+    }
+
+    pub fn bar_checks(&self) -> bool {
+        self.bar_checks
+    }
+
+    /// Sets the text written between the pieces of a note tied across groupings, which is `" ~ "`
+    /// by default. A tie forced at the end of a note is written without the trailing whitespace.
+    pub fn with_tie(mut self, tie: &str) -> Self {
+        self.tie = tie.to_string();
+        self
+    }
+
+    pub fn tie(&self) -> &str {
+        &self.tie
+    }
+
+    /// Starts a new line of compact output after every `every` measures, so that a long passage
+    /// isn't written as a single line. Pretty output already starts every measure on a line of
+    /// its own, and is left as it is. Passing 0 turns line breaks off.
+    pub fn with_line_breaks(mut self, every: u32) -> Self {
+        self.line_breaks = if every == 0 { None } else { Some(every) };
+        self
+    }
+
+    pub fn line_breaks(&self) -> Option<u32> {
+        self.line_breaks
+    }
+
+    /// Number of the measure at the current position, counting from 1, or 0 before the first
+    /// `Measure` has begun.
+    pub fn measure_number(&self) -> u32 {
+        self.measures
+    }
+
+    pub fn current(&self) -> Result<&ControlledGrouping<D, G>, &'static str> {
         self.stack.last().ok_or("No more groupings in the stack")
     }
 
-    pub fn current_mut(&mut self) -> Result<&mut ControlledGrouping<D>, &'static str> {
+    pub fn current_mut(&mut self) -> Result<&mut ControlledGrouping<D, G>, &'static str> {
         self.stack.last_mut().ok_or("No more groupings in the stack")
     }
 
@@ -189,33 +1081,90 @@ where D: Durational
         }
     }
 
-    fn advance_grouping(&mut self) -> Result<Vec<Box<Grouping<D>>>, &'static str> {
+    fn advance_grouping(&mut self) -> Result<Vec<G>, &'static str> {
         let mut out = Vec::new();
         // Pop the current element off the stack. It will eventually be returned, so that the view
         // can call end_annotation() in the proper order. Inner-nested groupings get popped first.
         out.push(self.stack.pop().ok_or("No stack remaining")?.grouping);
 
-        // If the stack is empty, replentish it with something from the queue
+        // If the stack is empty, replentish it with something from the queue. Once the queue has
+        // run dry as well there is nothing left to advance to, and any further time consumed
+        // will be reported as an error.
         if self.stack.is_empty() {
-            let next_grouping: ControlledGrouping<D> = self.queue.next()
-                .ok_or("Queue is empty")?.into();
-            self.stack.push(next_grouping);
+            match self.next_queued() {
+                Some(next_grouping) => self.push(next_grouping),
+                None => return Ok(out)
+            }
+        }
+
+        // If the top item on the stack has sub-groupings, add them to the stack
+        if !self.current()?.grouping.is_empty() {
+            self.descend()?;
         }
 
         // If the top item on the stack is empty of groupings and there is no time left
-        if self.current()?.grouping.is_empty() {
-            if self.current()?.left.as_float() == 0.0 {
-                // recur
-                out.extend(self.advance_grouping()?);
+        if self.current()?.grouping.is_empty() && self.current()?.left.as_float() == 0.0 {
+            // recur
+            out.extend(self.advance_grouping()?);
+        }
+
+        Ok(out)
+    }
+
+    /// Pushes the next sub-grouping of the current grouping onto the stack, and then the next
+    /// sub-grouping of that one, until reaching a grouping with no sub-groupings. Sub-groupings
+    /// without any duration are skipped.
+    fn descend(&mut self) -> Result<(), &'static str> {
+        while let Some(sub_grouping) = self.current_mut()?.grouping.next() {
+            if has_duration(&sub_grouping) {
+                self.push(sub_grouping);
             }
+        }
+        Ok(())
+    }
+
+    /// Pushes a grouping onto the stack, counting it if it begins a new measure.
+    fn push(&mut self, grouping: G) {
+        if grouping.is_measure() {
+            self.measures += 1;
+        }
+        self.stack.push(grouping.into());
+    }
+}
+
+impl<D, G> GroupingController<D, G> 
+where D: Durational,
+      G: GroupingNode<D> + Clone + 'static
+{
+    /// Saves the current position, including how much time is left in each `Grouping` on the
+    /// stack. A controller made with `from_vec()` only notes how far through its groupings it is.
+    /// Otherwise the queue is an iterator, and saving collects the rest of it into a `Vec` (which
+    /// the controller then carries on from), so the queue must be finite.
+    pub fn save(&mut self) -> Checkpoint<D, G> {
+        let queue: Vec<G> = if self.buffer.is_some() {
+            Vec::new()
         } else {
-            // ... else, add it to the stack
-            let next_grouping: ControlledGrouping<D> = self.current_mut()?.grouping
-                .next().expect("Inspect advance_grouping; should not be called.").into();
-            self.stack.push(next_grouping);
+            let queue: Vec<G> = self.queue.by_ref().collect();
+            self.queue = Box::new(queue.clone().into_iter());
+            queue
+        };
+        Checkpoint {
+            stack: self.stack.clone(),
+            queue,
+            cursor: self.cursor,
+            measures: self.measures
         }
+    }
 
-        Ok(out)
+    /// Returns to a position saved with `save()`. The same `Checkpoint` can be restored any
+    /// number of times.
+    pub fn restore(&mut self, checkpoint: &Checkpoint<D, G>) {
+        self.stack = checkpoint.stack.clone();
+        if self.buffer.is_none() {
+            self.queue = Box::new(checkpoint.queue.clone().into_iter());
+        }
+        self.cursor = checkpoint.cursor;
+        self.measures = checkpoint.measures;
     }
 }
 
@@ -223,10 +1172,13 @@ where D: Durational
 mod tests {
     use super::*;
     use super::super::*;
+    use super::super::notes::*;
+    use super::super::scrittore::*;
+    use serde_test::{Token, assert_ser_tokens};
 
     #[test]
     fn test_count_left() {
-        let groupings: Vec<Box<Grouping<RatioDuration>>> = vec![
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
             Box::new(Beat::new_ratio(1, 4)),
             Box::new(Beat::new_ratio(1, 2))
         ];
@@ -240,9 +1192,112 @@ mod tests {
         assert_eq!(controller.current().unwrap().left, Duration(RatioDuration(3, 8)));
     }
 
+    #[test]
+    fn test_beat_from_duration() {
+        let remainder = Duration(RatioDuration(3, 4)) - Duration(RatioDuration(1, 8));
+        let beat: Beat<RatioDuration> = Beat::new(remainder);
+        assert_eq!(beat.duration().as_ratio(), (5, 8));
+        assert_eq!(Beat::new(Duration(RatioDuration(1, 4))), Beat::new_ratio(1, 4));
+    }
+
+    #[test]
+    fn test_from_vec_reset() {
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Measure::from_time_signature(2, 4)),
+            Box::new(Beat::new_ratio(0, 1)),
+            Box::new(Measure::from_time_signature(3, 4))
+        ];
+        let mut controller = GroupingController::from_vec(groupings).unwrap();
+        controller.consume_time(Duration::new(5, 8)).unwrap();
+        assert_eq!(controller.measure_number(), 2);
+
+        // The checkpoint holds the index of the next grouping rather than the rest of the queue
+        let checkpoint = controller.save();
+        assert!(checkpoint.queue.is_empty());
+        assert_eq!(checkpoint.cursor, 3);
+        controller.consume_time(Duration::new(5, 8)).unwrap();
+        assert!(controller.consume_time(Duration::new(1, 4)).is_err());
+        controller.restore(&checkpoint);
+        assert_eq!(controller.measure_remaining(), Duration::new(5, 8));
+
+        controller.reset().unwrap();
+        assert_eq!(controller.measure_number(), 1);
+        assert_eq!(controller.measure_remaining(), Duration::new(1, 2));
+        assert!(controller.consume_time(Duration::new(5, 4)).is_ok());
+
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![Box::new(Beat::new_ratio(1, 4))];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        assert!(controller.reset().is_err());
+        let empty: Vec<Box<dyn Grouping<RatioDuration>>> = vec![];
+        assert!(GroupingController::from_vec(empty).is_err());
+    }
+
+    #[test]
+    fn test_starting_groupings() {
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Measure::from_time_signature(2, 4)),
+            Box::new(Measure::from_time_signature(2, 4))
+        ];
+        let mut controller = GroupingController::from_vec(groupings).unwrap();
+        assert_eq!(controller.starting().count(), 2);
+
+        controller.consume_time(Duration(RatioDuration(1, 4))).unwrap();
+        let started: Vec<_> = controller.starting().collect();
+        assert_eq!(started.len(), 1);
+        assert!(!started[0].is_measure());
+
+        controller.consume_time(Duration(RatioDuration(1, 8))).unwrap();
+        assert_eq!(controller.starting().count(), 0);
+
+        // Crossing into the second measure starts both it and its first beat
+        let ended = controller.consume_time(Duration(RatioDuration(1, 8))).unwrap();
+        assert!(ended.last().unwrap().is_measure());
+        let started: Vec<_> = controller.starting().collect();
+        assert_eq!(started.len(), 2);
+        assert!(started[0].is_measure());
+        assert_eq!(started[0].numbered_start_annotation(controller.measure_number()), measure_comment(2));
+    }
+
+    #[test]
+    fn test_measure_annotations() {
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Measure::from_time_signature(2, 4).with_start_annotation(" % bar\n ")),
+            Box::new(Measure::from_time_signature(2, 4).with_start_annotation("").with_end_annotation(" \\bar \"!\"\n "))
+        ];
+        let mut controller = GroupingController::from_vec(groupings).unwrap();
+        assert_eq!(controller.starting().next().unwrap().numbered_start_annotation(1), " % bar\n ");
+        let ended = controller.consume_time(Duration(RatioDuration(1, 2))).unwrap();
+        assert_eq!(ended.last().unwrap().end_annotation(), " |\n ");
+        let ended = controller.consume_time(Duration(RatioDuration(1, 2))).unwrap();
+        assert_eq!(ended.last().unwrap().end_annotation(), " \\bar \"!\"\n ");
+
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Measure::from_time_signature(2, 4).with_start_annotation(" % bar\n ").with_mark(BoundaryMark::Fermata)),
+            Box::new(Measure::from_time_signature(2, 4).with_start_annotation(""))
+        ];
+        let mut controller = GroupingController::from_vec(groupings).unwrap();
+        let notes = Notes::from_lilypond("c4 d e f").unwrap();
+        assert_eq!(format_notes(&notes, &mut controller).unwrap(), " % bar\n c4 d4 \\fermata |\n  e4 f4 |\n ");
+
+        // Compact output leaves out the numbered comment, but not a start annotation of its own
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Measure::from_time_signature(2, 4)),
+            Box::new(Measure::from_time_signature(3, 4).with_start_annotation(" \\time 3/4 "))
+        ];
+        let mut controller = GroupingController::from_vec(groupings).unwrap().with_style(OutputStyle::Compact);
+        let notes = Notes::from_lilypond("c4 d e f g").unwrap();
+        assert_eq!(format_notes(&notes, &mut controller).unwrap(), "c4 d4 | \\time 3/4 e4 f4 g4 |");
+
+        let measure: Measure<RatioDuration> = Measure::from_time_signature(2, 4).with_end_annotation(" ||| ");
+        let json = serde_json::to_string(&measure).unwrap();
+        let read: Measure<RatioDuration> = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.end_annotation(), " ||| ");
+        assert_eq!(read.start_annotation(), " %m. \n ");
+    }
+
     #[test]
     fn test_err_when_queue_is_empty() {
-        let groupings: Vec<Box<Grouping<RatioDuration>>> = vec![
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
             Box::new(Beat::new_ratio(1, 4))
         ];
         let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
@@ -251,6 +1306,26 @@ mod tests {
         assert!(res.is_err());
     }
 
+    #[test]
+    fn test_consume_time_across_measures() {
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Measure::from_contents(vec![
+                Box::new(Beat::new_ratio(1, 4)),
+                Box::new(Beat::new_ratio(1, 4))
+            ])),
+            Box::new(Measure::from_contents(vec![
+                Box::new(Beat::new_ratio(1, 4)),
+                Box::new(Beat::new_ratio(1, 4))
+            ]))
+        ];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        let out = controller.consume_time(Duration::new(1, 1)).unwrap();
+        let durations: Vec<(u32, u32)> = out.iter().map(|g| g.duration().as_ratio()).collect();
+        assert_eq!(durations, vec![(1, 4), (1, 4), (1, 2), (1, 4), (1, 4), (1, 2)]);
+        assert_eq!(out.iter().filter(|g| g.end_annotation() == " |\n ").count(), 2);
+        assert!(controller.current().is_err());
+    }
+
     #[test]
     fn test_measure_duration() {
         let measure: Measure<RatioDuration> = Measure::from_contents(vec![
@@ -263,9 +1338,183 @@ mod tests {
         assert_eq!(measure.duration().as_ratio(), (3, 4));
     }
 
+    #[test]
+    fn test_time_signature_beats() {
+        let beats = |mut measure: Measure<RatioDuration>| {
+            let mut out = vec![];
+            while let Some(beat) = Grouping::next(&mut measure) {
+                out.push(beat.duration().as_ratio());
+            }
+            out
+        };
+        assert_eq!(beats(Measure::from_time_signature(3, 4)), vec![(1, 4), (1, 4), (1, 4)]);
+        assert_eq!(beats(Measure::from_time_signature(6, 8)), vec![(3, 8), (3, 8)]);
+        assert_eq!(beats(Measure::from_time_signature(9, 8)), vec![(3, 8), (3, 8), (3, 8)]);
+        assert_eq!(beats(Measure::from_time_signature(3, 8)), vec![(1, 8), (1, 8), (1, 8)]);
+    }
+
+    #[test]
+    fn test_compound_meter_ties_at_dotted_beat() {
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Measure::from_time_signature(6, 8))
+        ];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        let notes = Notes::new(vec![
+            SingleNote::<ETPitch, RatioDuration>::new(60, RatioDuration(1, 4)),
+            SingleNote::new(62, RatioDuration(1, 4)),
+            SingleNote::new(64, RatioDuration(1, 4))
+        ]);
+        let out = format_notes(&notes, &mut controller).unwrap();
+        assert_eq!(" %m. 1\n c4 d8 ~ d8 e4 |\n ", &out);
+    }
+
+    #[test]
+    fn test_measure_remaining() {
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Region::new("", "", vec![
+                Box::new(Measure::from_time_signature(3, 4))
+            ]))
+        ];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        controller.consume_time(Duration::new(1, 4)).unwrap();
+        assert_eq!(controller.current().unwrap().left, Duration::new(1, 4));
+        assert_eq!(controller.measure_remaining(), Duration::new(1, 2));
+    }
+
+    #[test]
+    fn test_save_and_restore() {
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Measure::from_time_signature(3, 4)),
+            Box::new(Measure::from_time_signature(2, 4))
+        ];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        controller.consume_time(Duration::new(1, 8)).unwrap();
+        let checkpoint = controller.save();
+        assert_eq!(controller.current().unwrap().left, Duration::new(1, 8));
+
+        // Trial passage running into the second measure
+        controller.consume_time(Duration::new(7, 8)).unwrap();
+        assert_eq!(controller.measure_number(), 2);
+        assert_eq!(controller.measure_remaining(), Duration::new(1, 4));
+
+        controller.restore(&checkpoint);
+        assert_eq!(controller.current().unwrap().left, Duration::new(1, 8));
+        assert_eq!(controller.measure_number(), 1);
+        assert_eq!(controller.measure_remaining(), Duration::new(5, 8));
+
+        // The queue is restored too, so the same passage can be tried again
+        controller.consume_time(Duration::new(7, 8)).unwrap();
+        assert_eq!(controller.measure_remaining(), Duration::new(1, 4));
+        controller.restore(&checkpoint);
+        assert!(controller.consume_time(Duration::new(9, 8)).is_ok());
+        assert!(controller.consume_time(Duration::new(1, 8)).is_err());
+    }
+
+    #[test]
+    fn test_zero_durations() {
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Beat::new_ratio(0, 1)),
+            Box::new(Measure::from_contents(vec![
+                Box::new(Beat::new_ratio(0, 1)),
+                Box::new(Beat::new_ratio(1, 4)),
+                Box::new(Region::new("\\once ", "", vec![Box::new(Beat::new_ratio(0, 1))])),
+                Box::new(Beat::new_ratio(1, 4)),
+                Box::new(Beat::new_ratio(0, 1))
+            ])),
+            Box::new(Beat::new_ratio(0, 1)),
+            Box::new(Measure::from_contents(vec![
+                Box::new(Beat::new_ratio(1, 2))
+            ]))
+        ];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        assert_eq!(controller.consume_time(Duration::new(0, 1)).unwrap().len(), 0);
+        let notes = Notes::new(vec![
+            SingleNote::<ETPitch, RatioDuration>::new(60, RatioDuration(1, 4)),
+            SingleNote::new(62, RatioDuration(1, 2)),
+            SingleNote::new(64, RatioDuration(1, 4))
+        ]);
+        let out = format_notes(&notes, &mut controller).unwrap();
+        assert_eq!(" %m. 1\n c4 d4 ~  |\n  %m. 2\n d4 e4 |\n ", &out);
+
+        let mut controller = GroupingController::new(Box::new(vec![
+            Box::new(Beat::new_ratio(1, 4)) as Box<dyn Grouping<RatioDuration>>
+        ].into_iter())).unwrap();
+        let silent = SingleNote::<ETPitch, RatioDuration>::new(60, RatioDuration(0, 1));
+        assert!(format_note(&silent, &mut controller).is_err());
+        assert_eq!(controller.current().unwrap().left, Duration::new(1, 4));
+    }
+
+    #[test]
+    fn test_measure_numbers() {
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = (0..3)
+            .map(|_| Box::new(Measure::from_time_signature(2, 4)) as Box<dyn Grouping<RatioDuration>>)
+            .collect();
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        assert_eq!(controller.measure_number(), 1);
+        let notes = Notes::new(vec![
+            SingleNote::<ETPitch, RatioDuration>::new(60, RatioDuration(1, 1)),
+            SingleNote::new(62, RatioDuration(1, 2))
+        ]);
+        let out = format_notes(&notes, &mut controller).unwrap();
+        assert_eq!(" %m. 1\n c4 ~ c4 ~  |\n  %m. 2\n c4 ~ c4 |\n   %m. 3\n d4 ~ d4 |\n ", &out);
+    }
+
+    #[test]
+    fn test_double_barline() {
+        let measure: Measure<RatioDuration> = Measure::from_contents(vec![
+            Box::new(Beat::new_ratio(1, 4)),
+            Box::new(Beat::new_ratio(1, 4))
+        ]).with_barline(BarlineType::Double);
+        assert_eq!(measure.end_annotation(), " \\bar \"||\"\n ");
+    }
+
+    #[test]
+    fn test_final_barline() {
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Measure::from_contents(vec![
+                Box::new(Beat::new_ratio(1, 4)),
+                Box::new(Beat::new_ratio(1, 4))
+            ]).with_barline(BarlineType::Final))
+        ];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        let out = controller.consume_time(Duration::new(1, 2)).unwrap();
+        assert_eq!(out.last().unwrap().end_annotation(), " \\bar \"|.\"\n ");
+    }
+
+    #[test]
+    fn test_measure_checked() {
+        let quarters = |count| -> Vec<Box<dyn Grouping<RatioDuration>>> {
+            (0..count).map(|_| Box::new(Beat::new_ratio(1, 4)) as Box<dyn Grouping<RatioDuration>>).collect()
+        };
+        assert!(Measure::from_contents_checked(quarters(5), Duration::new(4, 4)).is_err());
+        assert!(Measure::from_contents_checked(quarters(3), Duration::new(4, 4)).is_err());
+        let measure = Measure::from_contents_checked(quarters(4), Duration::new(4, 4)).unwrap();
+        assert_eq!(measure.duration(), Duration::new(1, 1));
+    }
+
+    #[test]
+    fn test_boundary_marks() {
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Measure::from_contents(vec![
+                Box::new(Beat::new_ratio(1, 4).with_mark(BoundaryMark::Breathe)),
+                Box::new(Beat::new_ratio(1, 4))
+            ]).with_mark(BoundaryMark::Fermata).with_barline(BarlineType::Final))
+        ];
+        let mut controller = GroupingController::new(Box::new(groupings.clone().into_iter())).unwrap();
+        let out = controller.consume_time(Duration::new(1, 4)).unwrap();
+        assert_eq!(out[0].end_annotation(), " \\breathe");
+        let out = controller.consume_time(Duration::new(1, 4)).unwrap();
+        assert_eq!(out.last().unwrap().end_annotation(), " \\fermata \\bar \"|.\"\n ");
+
+        // The marks survive a round trip
+        let json = serde_json::to_string(&groupings[0].to_serialized().unwrap()).unwrap();
+        let read: Box<dyn Grouping<RatioDuration>> = serde_json::from_str::<SerializedGrouping<RatioDuration>>(&json).unwrap().into();
+        assert_eq!(read.end_annotation(), " \\fermata \\bar \"|.\"\n ");
+    }
+
     #[test]
     fn test_consume_time_stack_output() {
-        let groupings: Vec<Box<Grouping<RatioDuration>>> = vec![
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
             Box::new(Measure::from_contents(vec![
                 Box::new(Beat::new_ratio(1, 4)),
                 Box::new(Beat::new_ratio(1, 4))
@@ -277,7 +1526,7 @@ mod tests {
         ];
         let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
         let out = controller.consume_time(Duration::new(1, 2)).unwrap();
-        let exp: Vec<Box<Grouping<RatioDuration>>> = vec![
+        let exp: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
             Box::new(Beat::new_ratio(1, 4)),
             Box::new(Beat::new_ratio(1, 4)),
             Box::new(Measure::from_contents(vec![
@@ -296,7 +1545,7 @@ mod tests {
 
     #[test]
     fn test_groupings_stack() {
-        let groupings: Vec<Box<Grouping<RatioDuration>>> = vec![
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
             Box::new(
                 Measure::from_contents(
                     vec![
@@ -325,4 +1574,233 @@ mod tests {
         assert_eq!(controller.stack[0].left, Duration(RatioDuration(1, 4)));
         assert_eq!(controller.stack[1].left, Duration(RatioDuration(1, 4)));
     }
+
+    #[test]
+    fn test_stem_length_region() {
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Region::stem_length(5.5, vec![
+                Box::new(Beat::new_ratio(1, 4)),
+                Box::new(Beat::new_ratio(1, 4))
+            ])),
+            Box::new(Beat::new_ratio(1, 4))
+        ];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        let notes = Notes::new(vec![
+            SingleNote::<ETPitch, RatioDuration>::new(60, RatioDuration(1, 4)),
+            SingleNote::new(62, RatioDuration(1, 4)),
+            SingleNote::new(64, RatioDuration(1, 4))
+        ]);
+        let out = format_notes(&notes, &mut controller).unwrap();
+        assert_eq!("\\override Stem.length = #5.5 c4 d4 \\revert Stem.length  e4", &out);
+    }
+
+    #[test]
+    fn test_repeat_with_alternative() {
+        let measure = || -> Box<dyn Grouping<RatioDuration>> {
+            Box::new(Measure::from_contents(vec![
+                Box::new(Beat::new_ratio(1, 4)),
+                Box::new(Beat::new_ratio(1, 4))
+            ]))
+        };
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Repeat::volta(2, vec![measure(), measure()])
+                     .with_alternatives(vec![measure()]))
+        ];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        let notes = Notes::new(vec![
+            SingleNote::<ETPitch, RatioDuration>::new(60, RatioDuration(1, 4)),
+            SingleNote::new(62, RatioDuration(1, 4)),
+            SingleNote::new(64, RatioDuration(1, 2)),
+            SingleNote::new(65, RatioDuration(1, 2))
+        ]);
+        let out = format_notes(&notes, &mut controller).unwrap();
+        assert_eq!("\\repeat volta 2 {  %m. 1\n c4 d4 |\n   %m. 2\n e4 ~ e4 |\n  } \\alternative \
+                    { {  %m. 3\n f4 ~ f4 |\n  }  } ", &out);
+    }
+
+    #[test]
+    fn test_tie_into_alternative() {
+        let measure = || -> Box<dyn Grouping<RatioDuration>> { Box::new(Measure::from_time_signature(2, 4)) };
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Repeat::volta(2, vec![measure()]).with_alternatives(vec![measure(), measure()]))
+        ];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        let notes = Notes::new(vec![
+            SingleNote::<ETPitch, RatioDuration>::new(60, RatioDuration(1, 4)),
+            SingleNote::new(62, RatioDuration(1, 2)),
+            SingleNote::new(64, RatioDuration(1, 4)),
+            SingleNote::new(62, RatioDuration(1, 4)).with_annotation("\\repeatTie"),
+            SingleNote::new(64, RatioDuration(1, 4))
+        ]);
+        let out = format_notes(&notes, &mut controller).unwrap();
+        // The tie closes the body of the repeat, and the rest of the note opens the first ending
+        assert_eq!("\\repeat volta 2 {  %m. 1\n c4 d4 ~  |\n } \\alternative \
+                    { {  %m. 2\n d4 e4 |\n  }  {  %m. 3\n d4\\repeatTie e4 |\n  }  } ", &out);
+    }
+
+    #[test]
+    fn test_repeat_with_pickup() {
+        let phrase = vec![Measure::from_time_signature(4, 4), Measure::from_time_signature(4, 4)];
+        let repeat = Repeat::with_pickup(2, Duration(RatioDuration(1, 4)), phrase).unwrap();
+        assert_eq!(repeat.duration().as_ratio(), (2, 1));
+
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(repeat),
+            Box::new(Measure::from_time_signature(4, 4).with_barline(BarlineType::Final))
+        ];
+        let mut controller = GroupingController::from_vec(groupings).unwrap()
+            .with_style(OutputStyle::Compact);
+        let notes = Notes::from_lilypond("g4 c d e f g a b c d e f").unwrap();
+        assert_eq!(format_notes(&notes, &mut controller).unwrap(), 
+                   "\\repeat volta 2 { \\partial 4 g4 | c4 d4 e4 f4 | \
+                    g4 a4 b4 \\set Timing.measurePosition = #(ly:make-moment 0) | } c4 d4 e4 f4 \\bar \"|.\"");
+        assert_eq!(controller.measure_number(), 3);
+
+        let short = vec![Measure::from_time_signature(1, 4)];
+        assert!(Repeat::with_pickup(2, Duration(RatioDuration(1, 4)), short).is_err());
+        let measure: Measure<RatioDuration> = Measure::from_time_signature(3, 4).shortened(Duration(RatioDuration(3, 8))).unwrap();
+        assert_eq!(measure.duration().as_ratio(), (3, 8));
+    }
+
+    #[test]
+    fn test_nested_tuplets() {
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![
+            Box::new(Tuplet::new((3, 2), vec![
+                Box::new(Beat::new_ratio(1, 12)),
+                Box::new(Tuplet::new((2, 3), vec![
+                    Box::new(Beat::new_ratio(1, 16)),
+                    Box::new(Beat::new_ratio(1, 16))
+                ])),
+                Box::new(Beat::new_ratio(1, 24))
+            ]))
+        ];
+        assert_eq!(groupings[0].duration().as_ratio(), (1, 4));
+
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        let notes = Notes::new(vec![
+            SingleNote::<ETPitch, RatioDuration>::new(60, RatioDuration(1, 12)),
+            SingleNote::new(62, RatioDuration(1, 16)),
+            SingleNote::new(64, RatioDuration(1, 16)),
+            SingleNote::new(65, RatioDuration(1, 24))
+        ]);
+        let out = format_notes(&notes, &mut controller).unwrap();
+        assert_eq!("\\tuplet 3/2 { c8 \\tuplet 2/3 { d16 e16 }  f16 } ", &out);
+    }
+
+    #[test]
+    fn test_tuplet_bracket_and_number() {
+        let triplet = || Tuplet::new((3, 2), vec![
+            Box::new(Beat::new_ratio(1, 12)) as Box<dyn Grouping<RatioDuration>>,
+            Box::new(Beat::new_ratio(1, 12)),
+            Box::new(Beat::new_ratio(1, 12))
+        ]);
+        assert_eq!(triplet().start_annotation(), "\\tuplet 3/2 { ");
+        assert_eq!(triplet().with_number(TupletNumber::Fraction).start_annotation(), 
+                   "\\once \\override TupletNumber.text = #tuplet-number::calc-fraction-text \\tuplet 3/2 { ");
+
+        let groupings: Vec<Box<dyn Grouping<RatioDuration>>> = vec![Box::new(triplet().without_bracket())];
+        let mut controller = GroupingController::new(Box::new(groupings.into_iter())).unwrap();
+        let notes = Notes::new(vec![
+            SingleNote::<ETPitch, RatioDuration>::new(60, RatioDuration(1, 12)),
+            SingleNote::new(62, RatioDuration(1, 12)),
+            SingleNote::new(64, RatioDuration(1, 12))
+        ]);
+        let out = format_notes(&notes, &mut controller).unwrap();
+        assert_eq!("\\once \\override TupletBracket.bracket-visibility = ##f \\tuplet 3/2 { c8 d8 e8 } ", &out);
+    }
+
+    #[test]
+    fn test_tokens_measure() {
+        let measure: Measure<RatioDuration> = Measure::from_contents(vec![
+            Box::new(Beat::new_ratio(1, 4)),
+            Box::new(Beat::new_ratio(1, 4)),
+            Box::new(Beat::new_ratio(1, 4))
+        ]);
+        assert_ser_tokens(&measure, &[
+                      Token::Struct { name: "Measure", len: 2 },
+                      Token::Str("contents"),
+                      Token::Seq { len: Some(3) },
+                      Token::NewtypeVariant { name: "SerializedGrouping", variant: "Beat" },
+                      Token::Struct { name: "Beat", len: 1 },
+                      Token::Str("duration"),
+                      Token::NewtypeStruct { name: "Duration" },
+                      Token::TupleStruct { name: "RatioDuration", len: 2 },
+                      Token::U32(1),
+                      Token::U32(4),
+                      Token::TupleStructEnd,
+                      Token::StructEnd,
+                      Token::NewtypeVariant { name: "SerializedGrouping", variant: "Beat" },
+                      Token::Struct { name: "Beat", len: 1 },
+                      Token::Str("duration"),
+                      Token::NewtypeStruct { name: "Duration" },
+                      Token::TupleStruct { name: "RatioDuration", len: 2 },
+                      Token::U32(1),
+                      Token::U32(4),
+                      Token::TupleStructEnd,
+                      Token::StructEnd,
+                      Token::NewtypeVariant { name: "SerializedGrouping", variant: "Beat" },
+                      Token::Struct { name: "Beat", len: 1 },
+                      Token::Str("duration"),
+                      Token::NewtypeStruct { name: "Duration" },
+                      Token::TupleStruct { name: "RatioDuration", len: 2 },
+                      Token::U32(1),
+                      Token::U32(4),
+                      Token::TupleStructEnd,
+                      Token::StructEnd,
+                      Token::SeqEnd,
+                      Token::Str("barline"),
+                      Token::UnitVariant { name: "BarlineType", variant: "Single" },
+                      Token::StructEnd
+        ]);
+    }
+
+    #[test]
+    fn test_round_trip_nested_measure() {
+        let measure: Measure<RatioDuration> = Measure::from_contents(vec![
+            Box::new(Measure::from_contents(vec![
+                Box::new(Beat::new_ratio(1, 4)),
+                Box::new(Beat::new_ratio(1, 2))
+            ])),
+            Box::new(Beat::new_ratio(1, 4))
+        ]).with_barline(BarlineType::Final);
+        let json = serde_json::to_string(&measure).unwrap();
+        let read: Measure<RatioDuration> = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.duration(), Duration(RatioDuration(1, 1)));
+        assert_eq!(read.end_annotation(), BarlineType::Final.as_lilypond());
+        assert_eq!(serde_json::to_string(&read).unwrap(), json);
+    }
+
+    #[test]
+    fn test_serialize_region_fails() {
+        let measure: Measure<RatioDuration> = Measure::from_contents(vec![
+            Box::new(Region::new("", "", vec![Box::new(Beat::new_ratio(1, 4))]))
+        ]);
+        assert!(serde_json::to_string(&measure).is_err());
+    }
+
+    #[test]
+    fn test_static_groupings_match_boxed() {
+        let boxed: Vec<Box<dyn Grouping<RatioDuration>>> = (0..2).map(|_| {
+            Box::new(Measure::from_contents(vec![
+                Box::new(Beat::new_ratio(1, 4)),
+                Box::new(Beat::new_ratio(1, 4))
+            ])) as Box<dyn Grouping<RatioDuration>>
+        }).collect();
+        let fixed: Vec<StaticGrouping<RatioDuration>> = (0..2).map(|_| {
+            StaticGrouping::measure(vec![
+                StaticGrouping::beat(1, 4),
+                StaticGrouping::beat(1, 4)
+            ])
+        }).collect();
+        let notes = Notes::new(vec![
+            SingleNote::<ETPitch, RatioDuration>::new(60, RatioDuration(1, 4)),
+            SingleNote::new(62, RatioDuration(1, 2)),
+            SingleNote::new(64, RatioDuration(1, 4))
+        ]);
+
+        let mut controller = GroupingController::new(Box::new(boxed.into_iter())).unwrap();
+        let expected = format_notes(&notes, &mut controller).unwrap();
+        let mut controller = GroupingController::new(Box::new(fixed.into_iter())).unwrap();
+        assert_eq!(format_notes(&notes, &mut controller).unwrap(), expected);
+    }
 }